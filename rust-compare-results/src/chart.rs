@@ -0,0 +1,160 @@
+use std::fmt::Write;
+
+use crate::log::Run;
+use crate::stats::PhaseStats;
+
+const WIDTH: f64 = 900.0;
+const PANEL_HEIGHT: f64 = 280.0;
+const MARGIN_LEFT: f64 = 60.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 70.0;
+const COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+
+// Render the comparison as an SVG document with two panels:
+// frame time over the course of each run, and p50/p99 per phase.
+pub fn render_svg(runs: &[Run], phases: &[String], stats: &[Vec<Option<PhaseStats>>], stall_ms: f64) -> String {
+    let height = 2.0 * (PANEL_HEIGHT + MARGIN_TOP + MARGIN_BOTTOM);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="sans-serif" font-size="12">"#
+    );
+    let _ = writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#);
+
+    timeline(&mut svg, runs, stall_ms);
+    phase_bars(&mut svg, runs, phases, stats, PANEL_HEIGHT + MARGIN_TOP + MARGIN_BOTTOM);
+
+    // Legend
+    for (i, run) in runs.iter().enumerate() {
+        let x = MARGIN_LEFT + i as f64 * 140.0;
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{x}" y="8" width="12" height="12" fill="{}"/><text x="{}" y="18">{}</text>"#,
+            COLORS[i % COLORS.len()],
+            x + 16.0,
+            escape(&run.label)
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Frame time against run time; spikes above the axis are clipped
+fn timeline(svg: &mut String, runs: &[Run], stall_ms: f64) {
+    let top = MARGIN_TOP;
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let end_ms = runs
+        .iter()
+        .filter_map(|run| run.samples.last().map(|s| s.time_ms))
+        .fold(1.0, f64::max);
+    let y_max = (stall_ms * 3.0).max(1.0);
+
+    axes(svg, top, y_max, "frame time (ms)");
+    let stall_y = top + PANEL_HEIGHT * (1.0 - stall_ms / y_max);
+    let _ = writeln!(
+        svg,
+        r##"<line x1="{MARGIN_LEFT}" y1="{stall_y:.1}" x2="{}" y2="{stall_y:.1}" stroke="#999" stroke-dasharray="4 3"/>"##,
+        WIDTH - MARGIN_RIGHT
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{MARGIN_LEFT}" y="{}">time (s), run length {:.1} s</text>"#,
+        top + PANEL_HEIGHT + 20.0,
+        end_ms / 1000.0
+    );
+
+    // Reports have no frames to draw
+    for (i, run) in runs.iter().enumerate().filter(|(_, run)| !run.samples.is_empty()) {
+        let mut points = String::new();
+        for sample in &run.samples {
+            let x = MARGIN_LEFT + plot_width * sample.time_ms / end_ms;
+            let y = top + PANEL_HEIGHT * (1.0 - sample.frame_ms.min(y_max) / y_max);
+            let _ = write!(points, "{:.1},{:.1} ", x, y);
+        }
+        let _ = writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1"/>"#,
+            points.trim_end(),
+            COLORS[i % COLORS.len()]
+        );
+    }
+}
+
+// One group per phase, one bar per run: the bar is p99, the tick is p50
+fn phase_bars(svg: &mut String, runs: &[Run], phases: &[String], stats: &[Vec<Option<PhaseStats>>], offset: f64) {
+    let top = offset + MARGIN_TOP;
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let y_max = stats
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|s| s.p99_ms)
+        .fold(1.0, f64::max)
+        * 1.1;
+
+    axes(svg, top, y_max, "p99 / p50 (ms)");
+
+    let group_width = plot_width / phases.len().max(1) as f64;
+    let bar_width = (group_width * 0.8) / runs.len().max(1) as f64;
+    for (p, phase) in phases.iter().enumerate() {
+        let group_x = MARGIN_LEFT + p as f64 * group_width + group_width * 0.1;
+        for (r, run_stats) in stats.iter().enumerate() {
+            let Some(s) = &run_stats[p] else { continue };
+            let x = group_x + r as f64 * bar_width;
+            let bar_top = top + PANEL_HEIGHT * (1.0 - s.p99_ms / y_max);
+            let p50_y = top + PANEL_HEIGHT * (1.0 - s.p50_ms / y_max);
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{x:.1}" y="{bar_top:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+                bar_width * 0.9,
+                top + PANEL_HEIGHT - bar_top,
+                COLORS[r % COLORS.len()]
+            );
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{x:.1}" y1="{p50_y:.1}" x2="{:.1}" y2="{p50_y:.1}" stroke="black" stroke-width="2"/>"#,
+                x + bar_width * 0.9
+            );
+        }
+        let label_x = group_x + group_width * 0.4;
+        let label_y = top + PANEL_HEIGHT + 14.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{label_x:.1}" y="{label_y:.1}" text-anchor="end" transform="rotate(-30 {label_x:.1} {label_y:.1})">{}</text>"#,
+            escape(phase)
+        );
+    }
+}
+
+fn axes(svg: &mut String, top: f64, y_max: f64, label: &str) {
+    let bottom = top + PANEL_HEIGHT;
+    let _ = writeln!(
+        svg,
+        r#"<line x1="{MARGIN_LEFT}" y1="{top}" x2="{MARGIN_LEFT}" y2="{bottom}" stroke="black"/><line x1="{MARGIN_LEFT}" y1="{bottom}" x2="{}" y2="{bottom}" stroke="black"/>"#,
+        WIDTH - MARGIN_RIGHT
+    );
+    for tick in 0..=4 {
+        let value = y_max * tick as f64 / 4.0;
+        let y = bottom - PANEL_HEIGHT * tick as f64 / 4.0;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{:.1}" text-anchor="end">{:.1}</text>"#,
+            MARGIN_LEFT - 6.0,
+            y + 4.0,
+            value
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="14" y="{:.1}" transform="rotate(-90 14 {:.1})" text-anchor="middle">{}</text>"#,
+        top + PANEL_HEIGHT / 2.0,
+        top + PANEL_HEIGHT / 2.0,
+        label
+    );
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+
+use crate::stats::PhaseStats;
+
+// One frame from a per-frame log.
+// Only the columns shared by the Rust and Racket logs are read; anything
+// else in the file is ignored.
+pub struct Sample {
+    pub time_ms: f64,
+    pub phase: String,
+    pub frame_ms: f64,
+    pub memory_mb: f64,
+}
+
+// The per-phase table of a run report (--report in the Rust stress tests):
+// `# key=value` metadata lines, then this header and one row per phase
+const REPORT_HEADER: &str = "phase,frames,p50_ms,p90_ms,p99_ms,max_ms,stalls,memory_delta_mb,memory_peak_mb";
+
+pub struct Report {
+    // The threshold the run counted its stalls at, from `# stall_ms=...`
+    pub stall_ms: Option<f64>,
+    pub phases: Vec<(String, PhaseStats)>,
+}
+
+// A labelled run, e.g. "racket=racket.csv". A per-frame log has samples; a
+// run report only has the figures per phase, so it has no samples.
+pub struct Run {
+    pub label: String,
+    pub samples: Vec<Sample>,
+    pub report: Option<Report>,
+}
+
+impl Run {
+    // Parse a "label=path" argument; a bare path is labelled by its file stem
+    pub fn load(arg: &str) -> Result<Run, String> {
+        let (label, path) = match arg.split_once('=') {
+            Some((label, path)) => (label.to_string(), Path::new(path)),
+            None => {
+                let path = Path::new(arg);
+                let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+                (stem.unwrap_or_else(|| arg.to_string()), path)
+            }
+        };
+
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if is_report(&text) {
+            let report = parse_report(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if report.phases.is_empty() {
+                return Err(format!("{}: no phases in report", path.display()));
+            }
+            return Ok(Run { label, samples: Vec::new(), report: Some(report) });
+        }
+
+        let samples = parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        if samples.is_empty() {
+            return Err(format!("{}: no frames in log", path.display()));
+        }
+
+        Ok(Run { label, samples, report: None })
+    }
+}
+
+fn is_report(text: &str) -> bool {
+    text.lines().find(|line| !line.trim().is_empty() && !line.starts_with('#')) == Some(REPORT_HEADER)
+}
+
+fn parse_report(text: &str) -> Result<Report, String> {
+    let stall_ms = text
+        .lines()
+        .find_map(|line| line.strip_prefix("# stall_ms="))
+        .and_then(|value| value.trim().parse().ok());
+
+    let mut phases = Vec::new();
+    let rows = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .skip(1);
+    for (index, line) in rows {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let bad = || format!("line {}: bad report row", index + 1);
+        if fields.len() != 9 {
+            return Err(bad());
+        }
+        let number = |i: usize| fields[i].parse::<f64>().map_err(|_| bad());
+        let count = |i: usize| fields[i].parse::<usize>().map_err(|_| bad());
+        phases.push((
+            fields[0].to_string(),
+            PhaseStats {
+                frames: count(1)?,
+                p50_ms: number(2)?,
+                p90_ms: number(3)?,
+                p99_ms: number(4)?,
+                max_ms: number(5)?,
+                stalls: count(6)?,
+                memory_delta_mb: number(7)?,
+                memory_peak_mb: number(8)?,
+            },
+        ));
+    }
+
+    Ok(Report { stall_ms, phases })
+}
+
+fn parse(text: &str) -> Result<Vec<Sample>, String> {
+    // Comment lines carry run metadata and are skipped
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+    let header: Vec<&str> = match lines.next() {
+        Some((_, line)) => line.split(',').map(str::trim).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .ok_or_else(|| format!("missing column '{}'", name))
+    };
+    let time_col = column("time_ms")?;
+    let phase_col = column("phase")?;
+    let frame_col = column("frame_ms")?;
+    let memory_col = column("memory_mb")?;
+
+    let mut samples = Vec::new();
+    for (index, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let number = |col: usize| -> Result<f64, String> {
+            fields
+                .get(col)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| format!("line {}: bad value in column {}", index + 1, header[col]))
+        };
+
+        samples.push(Sample {
+            time_ms: number(time_col)?,
+            phase: fields.get(phase_col).unwrap_or(&"").to_string(),
+            frame_ms: number(frame_col)?,
+            memory_mb: number(memory_col)?,
+        });
+    }
+
+    Ok(samples)
+}
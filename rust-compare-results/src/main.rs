@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod chart;
+mod log;
+mod stats;
+
+use log::Run;
+use stats::{phase_order, phase_stats, PhaseStats};
+
+const USAGE: &str = "\
+Usage: rust-compare-results [OPTIONS] <[LABEL=]LOG|REPORT>...
+
+Compares per-frame logs written with --log by the Rust and Racket stress
+tests, or run reports written with --report by the Rust ones, phase by
+phase. A report has no per-frame data: its figures are shown as the run
+computed them (stalls at its own --stall-ms, no --skip-frames) and it has
+no line in the chart's timeline. The chart is SVG only; convert it to PNG
+with e.g. rsvg-convert.
+
+Options:
+  --stall-ms <MS>       Frame time counted as a stall (default: 33.3)
+  --skip-frames <N>     Frames ignored after each phase switch (default: 0)
+  --svg <FILE>          Also render the comparison as an SVG chart
+  -h, --help            Print this help";
+
+struct Options {
+    stall_ms: f64,
+    skip_frames: usize,
+    svg_path: Option<PathBuf>,
+    logs: Vec<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        stall_ms: 1000.0 / 30.0,
+        skip_frames: 0,
+        svg_path: None,
+        logs: Vec::new(),
+    };
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--stall-ms" => {
+                options.stall_ms = value()?.parse().map_err(|_| "--stall-ms needs a number")?
+            }
+            "--skip-frames" => {
+                options.skip_frames = value()?.parse().map_err(|_| "--skip-frames needs a count")?
+            }
+            "--svg" => options.svg_path = Some(PathBuf::from(value()?)),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            _ => options.logs.push(arg),
+        }
+    }
+
+    if options.logs.is_empty() {
+        return Err("no logs given".to_string());
+    }
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut runs = Vec::new();
+    for arg in &options.logs {
+        match Run::load(arg) {
+            Ok(run) => runs.push(run),
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // stats[run][phase]
+    let phases = phase_order(&runs);
+    let stats: Vec<Vec<Option<PhaseStats>>> = runs
+        .iter()
+        .map(|run| {
+            phases
+                .iter()
+                .map(|phase| phase_stats(run, phase, options.stall_ms, options.skip_frames))
+                .collect()
+        })
+        .collect();
+
+    for run in &runs {
+        if let Some(stall_ms) = run.report.as_ref().and_then(|report| report.stall_ms) {
+            if (stall_ms - options.stall_ms).abs() > 0.05 {
+                println!("Note: {} counted its stalls at {:.1} ms", run.label, stall_ms);
+            }
+        }
+    }
+    print_table(&runs, &phases, &stats, options.stall_ms);
+
+    if let Some(path) = &options.svg_path {
+        let svg = chart::render_svg(&runs, &phases, &stats, options.stall_ms);
+        if let Err(e) = fs::write(path, svg) {
+            eprintln!("error: {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+        println!("Chart written to {}", path.display());
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn print_table(runs: &[Run], phases: &[String], stats: &[Vec<Option<PhaseStats>>], stall_ms: f64) {
+    let label_width = runs.iter().map(|r| r.label.len()).max().unwrap_or(0).max(6);
+
+    println!("Stall threshold: {:.1} ms", stall_ms);
+    for (p, phase) in phases.iter().enumerate() {
+        println!();
+        println!("Phase: {}", phase);
+        println!(
+            "  {:<label_width$} {:>7} {:>8} {:>8} {:>8} {:>8} {:>7} {:>9} {:>9}",
+            "run", "frames", "p50 ms", "p90 ms", "p99 ms", "max ms", "stalls", "mem Δ MB", "peak MB"
+        );
+        for (run, run_stats) in runs.iter().zip(stats) {
+            match &run_stats[p] {
+                Some(s) => println!(
+                    "  {:<label_width$} {:>7} {:>8.2} {:>8.2} {:>8.2} {:>8.2} {:>7} {:>+9.2} {:>9.2}",
+                    run.label,
+                    s.frames,
+                    s.p50_ms,
+                    s.p90_ms,
+                    s.p99_ms,
+                    s.max_ms,
+                    s.stalls,
+                    s.memory_delta_mb,
+                    s.memory_peak_mb
+                ),
+                None => println!("  {:<label_width$} {:>7}", run.label, "-"),
+            }
+        }
+    }
+}
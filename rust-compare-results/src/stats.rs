@@ -0,0 +1,81 @@
+use crate::log::{Run, Sample};
+
+// Frame time and memory figures for one phase of one run
+#[derive(Clone)]
+pub struct PhaseStats {
+    pub frames: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub stalls: usize,
+    pub memory_delta_mb: f64,
+    pub memory_peak_mb: f64,
+}
+
+// Phase names in order of first appearance, across all runs
+pub fn phase_order(runs: &[Run]) -> Vec<String> {
+    let mut phases: Vec<String> = Vec::new();
+    for run in runs {
+        let report_phases = run.report.iter().flat_map(|report| report.phases.iter().map(|(phase, _)| phase));
+        for phase in run.samples.iter().map(|sample| &sample.phase).chain(report_phases) {
+            if !phases.contains(phase) {
+                phases.push(phase.clone());
+            }
+        }
+    }
+    phases
+}
+
+// A run's figures for `phase`: summarized from a log, or as a report has them
+pub fn phase_stats(run: &Run, phase: &str, stall_ms: f64, skip_frames: usize) -> Option<PhaseStats> {
+    match &run.report {
+        Some(report) => report.phases.iter().find(|(name, _)| name == phase).map(|(_, stats)| stats.clone()),
+        None => summarize(&run.samples, phase, stall_ms, skip_frames),
+    }
+}
+
+// Summarize every frame of a run spent in `phase`.
+// A phase can be entered several times (e.g. stress toggled on, off and on
+// again); frame times are pooled over all visits, and the memory delta is
+// the sum of the growth during each visit. The first `skip_frames` frames
+// of each visit are left out of the frame time figures so the switch-over
+// frame doesn't count as a stall.
+pub fn summarize(samples: &[Sample], phase: &str, stall_ms: f64, skip_frames: usize) -> Option<PhaseStats> {
+    let mut frame_times = Vec::new();
+    let mut memory_delta_mb = 0.0;
+    let mut memory_peak_mb: f64 = 0.0;
+
+    for segment in samples.chunk_by(|a, b| a.phase == b.phase) {
+        if segment[0].phase != phase {
+            continue;
+        }
+        frame_times.extend(segment.iter().skip(skip_frames).map(|s| s.frame_ms));
+        memory_delta_mb += segment[segment.len() - 1].memory_mb - segment[0].memory_mb;
+        for sample in segment {
+            memory_peak_mb = memory_peak_mb.max(sample.memory_mb);
+        }
+    }
+
+    if frame_times.is_empty() {
+        return None;
+    }
+    frame_times.sort_by(f64::total_cmp);
+
+    Some(PhaseStats {
+        frames: frame_times.len(),
+        p50_ms: percentile(&frame_times, 50.0),
+        p90_ms: percentile(&frame_times, 90.0),
+        p99_ms: percentile(&frame_times, 99.0),
+        max_ms: frame_times[frame_times.len() - 1],
+        stalls: frame_times.iter().filter(|&&t| t > stall_ms).count(),
+        memory_delta_mb,
+        memory_peak_mb,
+    })
+}
+
+// Nearest-rank percentile of an ascending, non-empty slice
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,104 @@
+// Global allocator, selected with a cargo feature:
+//   cargo run                      # system allocator (glibc malloc on Linux)
+//   cargo run --features jemalloc
+//   cargo run --features mimalloc
+// With a profiler feature (see profiler.rs) it is wrapped to report its
+// allocations.
+
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("the jemalloc and mimalloc features are mutually exclusive");
+
+#[cfg(feature = "jemalloc")]
+type Base = tikv_jemallocator::Jemalloc;
+#[cfg(feature = "jemalloc")]
+const BASE: Base = tikv_jemallocator::Jemalloc;
+
+#[cfg(feature = "mimalloc")]
+type Base = mimalloc::MiMalloc;
+#[cfg(feature = "mimalloc")]
+const BASE: Base = mimalloc::MiMalloc;
+
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+type Base = std::alloc::System;
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+const BASE: Base = std::alloc::System;
+
+#[cfg(not(any(feature = "puffin", feature = "tracy")))]
+#[global_allocator]
+static GLOBAL: Base = BASE;
+
+#[cfg(feature = "puffin")]
+#[global_allocator]
+static GLOBAL: crate::profiler::Counting<Base> = crate::profiler::Counting(BASE);
+
+#[cfg(feature = "tracy")]
+#[global_allocator]
+static GLOBAL: tracy_client::ProfiledAllocator<Base> = tracy_client::ProfiledAllocator::new(BASE, 0);
+
+pub fn name() -> &'static str {
+    if cfg!(feature = "jemalloc") {
+        "jemalloc"
+    } else if cfg!(feature = "mimalloc") {
+        "mimalloc"
+    } else {
+        "system"
+    }
+}
+
+// Ask the allocator to return free memory to the OS.
+// This is the closest Rust gets to Racket's (collect-garbage): nothing is
+// freed that wasn't already dropped, but cached pages are released.
+// Returns false if the allocator has no way to do this.
+pub fn trim() -> bool {
+    #[cfg(feature = "jemalloc")]
+    {
+        // "arena.<MALLCTL_ARENAS_ALL>.purge"
+        let name = b"arena.4096.purge\0";
+        let result = unsafe {
+            tikv_jemalloc_sys::mallctl(
+                name.as_ptr() as *const _,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        result == 0
+    }
+
+    #[cfg(feature = "mimalloc")]
+    {
+        unsafe { libmimalloc_sys::mi_collect(true) };
+        true
+    }
+
+    #[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+    {
+        system_trim()
+    }
+}
+
+#[cfg(all(not(any(feature = "jemalloc", feature = "mimalloc")), target_os = "linux", target_env = "gnu"))]
+fn system_trim() -> bool {
+    unsafe { libc::malloc_trim(0) };
+    true
+}
+
+#[cfg(all(not(any(feature = "jemalloc", feature = "mimalloc")), target_os = "macos"))]
+fn system_trim() -> bool {
+    extern "C" {
+        fn malloc_zone_pressure_relief(zone: *mut libc::c_void, goal: libc::size_t) -> libc::size_t;
+    }
+    // A null zone with goal 0 means: all zones, as much as possible
+    unsafe { malloc_zone_pressure_relief(std::ptr::null_mut(), 0) };
+    true
+}
+
+#[cfg(all(
+    not(any(feature = "jemalloc", feature = "mimalloc")),
+    not(all(target_os = "linux", target_env = "gnu")),
+    not(target_os = "macos")
+))]
+fn system_trim() -> bool {
+    false
+}
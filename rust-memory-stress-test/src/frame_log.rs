@@ -0,0 +1,79 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::clock::ms;
+use crate::os_stats::{OsCounters, Smaps};
+use crate::timing::FrameTimes;
+
+// One row of the per-frame log.
+// The Racket script writes the same phase names, frame_ms and memory_mb
+// columns, so rust-compare-results can line both runs up.
+pub struct FrameRecord<'a> {
+    pub frame: u64,
+    pub time_ms: f64,
+    pub phase: &'a str,
+    pub times: FrameTimes,
+    pub memory_mb: f32,
+    pub os: OsCounters,
+    // Empty columns where smaps_rollup can't be read
+    pub smaps: Option<Smaps>,
+    // Empty when there is no memory limit (see limits.rs)
+    pub headroom_mb: Option<f32>,
+    pub allocation_failures: u64,
+}
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct FrameLog {
+    writer: BufWriter<File>,
+    flushed: Instant,
+}
+
+impl FrameLog {
+    pub fn create(path: &Path) -> io::Result<FrameLog> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,time_ms,phase,frame_ms,update_ms,stress_ms,draw_ms,swap_ms,wait_ms,memory_mb,minor_faults,major_faults,voluntary_switches,involuntary_switches,rss_mb,pss_mb,anonymous_mb,swap_mb,headroom_mb,allocation_failures")?;
+        Ok(FrameLog { writer, flushed: Instant::now() })
+    }
+
+    pub fn record(&mut self, record: &FrameRecord) -> io::Result<()> {
+        let smaps = match &record.smaps {
+            Some(s) => format!("{:.2},{:.2},{:.2},{:.2}", s.rss_mb, s.pss_mb, s.anonymous_mb, s.swap_mb),
+            None => ",,,".to_string(),
+        };
+        let headroom = record.headroom_mb.map_or(String::new(), |headroom| format!("{:.2}", headroom));
+        writeln!(
+            self.writer,
+            "{},{:.3},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.2},{},{},{},{},{},{},{}",
+            record.frame,
+            record.time_ms,
+            record.phase,
+            ms(record.times.frame_ns),
+            ms(record.times.update_ns),
+            ms(record.times.stress_ns),
+            ms(record.times.draw_ns),
+            ms(record.times.swap_ns),
+            ms(record.times.wait_ns),
+            record.memory_mb,
+            record.os.minor_faults,
+            record.os.major_faults,
+            record.os.voluntary_switches,
+            record.os.involuntary_switches,
+            smaps,
+            headroom,
+            record.allocation_failures,
+        )?;
+        // Keep at most a second of frames unwritten, for runs that end in an abort
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.flushed = Instant::now();
+        self.writer.flush()
+    }
+}
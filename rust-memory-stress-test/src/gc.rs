@@ -0,0 +1,256 @@
+use std::collections::{TryReserveError, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::profiler::scope;
+
+// A small mark-and-sweep heap standing in for a tracing GC.
+//
+// Every stress object is a heap object with a payload and, sometimes, a
+// reference to one of the currently rooted objects. The roots are the most
+// recent `retained` objects, like the `objects` Vec of the plain workload;
+// everything that falls out of that window (and isn't referenced from inside
+// it) becomes garbage. A collection starts once `threshold` bytes have been
+// allocated since the last one, and is either done in one stop-the-world
+// pause or spread over frames in slices of `slice` work units.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GcMode {
+    StopTheWorld,
+    Incremental,
+}
+
+impl GcMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GcMode::StopTheWorld => "stop-the-world",
+            GcMode::Incremental => "incremental",
+        }
+    }
+}
+
+struct GcObject {
+    payload: Vec<u8>,
+    link: Option<usize>,
+}
+
+enum Phase {
+    Idle,
+    Mark { stack: Vec<usize> },
+    Sweep { cursor: usize },
+}
+
+#[derive(Default)]
+pub struct GcStats {
+    pub collections: u64,
+    pub last_pause: Duration,
+    pub max_pause: Duration,
+}
+
+pub struct GcHeap {
+    pub mode: GcMode,
+    pub threshold: usize,
+    pub slice: usize,
+    slots: Vec<Option<GcObject>>,
+    marks: Vec<bool>,
+    free: Vec<usize>,
+    roots: VecDeque<usize>,
+    retained: usize,
+    live_objects: usize,
+    live_bytes: usize,
+    allocated_since_gc: usize,
+    phase: Phase,
+    link_state: u32,
+    pub stats: GcStats,
+}
+
+impl GcHeap {
+    pub fn new(mode: GcMode, threshold: usize, slice: usize, retained: usize) -> GcHeap {
+        GcHeap {
+            mode,
+            threshold,
+            slice: slice.max(1),
+            slots: Vec::new(),
+            marks: Vec::new(),
+            free: Vec::new(),
+            roots: VecDeque::new(),
+            retained,
+            live_objects: 0,
+            live_bytes: 0,
+            allocated_since_gc: 0,
+            phase: Phase::Idle,
+            link_state: 0x2545_f491,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn live_objects(&self) -> usize {
+        self.live_objects
+    }
+
+    pub fn live_bytes(&self) -> usize {
+        self.live_bytes
+    }
+
+    pub fn rooted(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn rooted_payloads(&self) -> impl Iterator<Item = &[u8]> {
+        self.roots.iter().filter_map(|&index| self.slots[index].as_ref()).map(|object| object.payload.as_slice())
+    }
+
+    pub fn collecting(&self) -> bool {
+        !matches!(self.phase, Phase::Idle)
+    }
+
+    // Allocate a rooted object; may trigger (or, when incremental, start) a collection.
+    // Fails without changing the heap when the memory isn't there.
+    pub fn allocate(&mut self, payload: Vec<u8>) -> Result<(), TryReserveError> {
+        let size = payload.len();
+        if self.free.is_empty() {
+            self.slots.try_reserve(1)?;
+            self.marks.try_reserve(1)?;
+        }
+        self.roots.try_reserve(1)?;
+
+        // About one object in four references a rooted one
+        let link = if self.next_random().is_multiple_of(4) && !self.roots.is_empty() {
+            let pick = self.next_random() as usize % self.roots.len();
+            Some(self.roots[pick])
+        } else {
+            None
+        };
+
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(None);
+                self.marks.push(false);
+                self.slots.len() - 1
+            }
+        };
+        self.slots[index] = Some(GcObject { payload, link });
+
+        // New objects are allocated black while a collection is running, and
+        // whatever they reference is shaded so it can't be swept from under them
+        match &mut self.phase {
+            Phase::Idle => self.marks[index] = false,
+            Phase::Mark { stack } => {
+                self.marks[index] = true;
+                if let Some(link) = link {
+                    stack.push(link);
+                }
+            }
+            Phase::Sweep { cursor } => self.marks[index] = index >= *cursor,
+        }
+
+        self.live_objects += 1;
+        self.live_bytes += size;
+        self.allocated_since_gc += size;
+
+        self.roots.push_back(index);
+        if self.roots.len() > self.retained {
+            self.roots.pop_front();
+        }
+
+        if self.allocated_since_gc >= self.threshold {
+            match (self.mode, &self.phase) {
+                (GcMode::StopTheWorld, _) => self.collect(),
+                (GcMode::Incremental, Phase::Idle) => self.start_cycle(),
+                // Allocation is outrunning the incremental collector: finish the cycle now
+                (GcMode::Incremental, _) if self.allocated_since_gc >= 2 * self.threshold => self.collect(),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Do one slice of incremental work; call once per frame
+    pub fn step(&mut self) {
+        if self.mode == GcMode::Incremental && self.collecting() {
+            scope!("gc_step");
+            let start = Instant::now();
+            self.work(self.slice);
+            self.record_pause(start.elapsed());
+        }
+    }
+
+    // Run a full collection (or finish the current one) in a single pause
+    pub fn collect(&mut self) {
+        scope!("gc_collect");
+        let start = Instant::now();
+        if !self.collecting() {
+            self.begin();
+        }
+        self.work(usize::MAX);
+        self.record_pause(start.elapsed());
+    }
+
+    pub fn clear(&mut self) {
+        *self = GcHeap::new(self.mode, self.threshold, self.slice, self.retained);
+    }
+
+    fn start_cycle(&mut self) {
+        let start = Instant::now();
+        self.begin();
+        self.record_pause(start.elapsed());
+    }
+
+    fn begin(&mut self) {
+        self.allocated_since_gc = 0;
+        self.phase = Phase::Mark { stack: self.roots.iter().copied().collect() };
+    }
+
+    // Mark and sweep up to `budget` objects
+    fn work(&mut self, mut budget: usize) {
+        while budget > 0 {
+            match &mut self.phase {
+                Phase::Idle => return,
+                Phase::Mark { stack } => match stack.pop() {
+                    Some(index) => {
+                        budget -= 1;
+                        if !self.marks[index] {
+                            self.marks[index] = true;
+                            if let Some(link) = self.slots[index].as_ref().and_then(|o| o.link) {
+                                stack.push(link);
+                            }
+                        }
+                    }
+                    None => self.phase = Phase::Sweep { cursor: 0 },
+                },
+                Phase::Sweep { cursor } => {
+                    if *cursor == self.slots.len() {
+                        self.phase = Phase::Idle;
+                        self.stats.collections += 1;
+                        return;
+                    }
+                    let index = *cursor;
+                    *cursor += 1;
+                    budget -= 1;
+                    if self.marks[index] {
+                        self.marks[index] = false;
+                    } else if let Some(object) = self.slots[index].take() {
+                        self.live_objects -= 1;
+                        self.live_bytes -= object.payload.len();
+                        self.free.push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_pause(&mut self, pause: Duration) {
+        self.stats.last_pause = pause;
+        self.stats.max_pause = self.stats.max_pause.max(pause);
+    }
+
+    // xorshift32; the heap's link pattern doesn't need a real RNG
+    fn next_random(&mut self) -> u32 {
+        let mut x = self.link_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.link_state = x;
+        x
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Memory limits the process runs under, and how close it is to them.
+//
+// An address space limit (RLIMIT_AS, `ulimit -v` or --limit-as-mb) makes
+// mmap and brk fail, so allocations past it fail and can be handled. A cgroup
+// v2 memory.max (a systemd scope or a container) doesn't: the kernel reclaims
+// what it can and then OOM-kills the process, so near it the only thing to do
+// is stop in time. Both are only looked for on Linux.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LimitKind {
+    AddressSpace,
+    Cgroup,
+}
+
+impl LimitKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LimitKind::AddressSpace => "RLIMIT_AS",
+            LimitKind::Cgroup => "cgroup memory.max",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MemoryLimit {
+    pub kind: LimitKind,
+    pub limit_bytes: u64,
+    // The cgroup's directory under /sys/fs/cgroup
+    cgroup: Option<PathBuf>,
+}
+
+const MB: f32 = 1024.0 * 1024.0;
+
+impl MemoryLimit {
+    // The tighter of the two limits, if there is any
+    pub fn detect() -> Option<MemoryLimit> {
+        let address_space = address_space_limit().map(|limit_bytes| MemoryLimit {
+            kind: LimitKind::AddressSpace,
+            limit_bytes,
+            cgroup: None,
+        });
+        let cgroup = cgroup_limit().map(|(cgroup, limit_bytes)| MemoryLimit {
+            kind: LimitKind::Cgroup,
+            limit_bytes,
+            cgroup: Some(cgroup),
+        });
+        // Headroom is what matters: the address space counts every mapping,
+        // the cgroup only memory in use
+        [address_space, cgroup].into_iter().flatten().min_by(|a, b| a.headroom_mb().total_cmp(&b.headroom_mb()))
+    }
+
+    pub fn limit_mb(&self) -> f32 {
+        self.limit_bytes as f32 / MB
+    }
+
+    // What counts toward the limit now
+    pub fn used_bytes(&self) -> u64 {
+        match (&self.kind, &self.cgroup) {
+            (LimitKind::Cgroup, Some(cgroup)) => read_number(&cgroup.join("memory.current")).unwrap_or(0),
+            _ => address_space_size().unwrap_or(0),
+        }
+    }
+
+    // MB left before the limit, negative once over it
+    pub fn headroom_mb(&self) -> f32 {
+        (self.limit_bytes as f64 - self.used_bytes() as f64) as f32 / MB
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} {:.0} MB", self.kind.name(), self.limit_mb())
+    }
+}
+
+// Lower this process's address space limit. Mappings that already exist stay,
+// so it only takes effect for what is allocated from now on.
+#[cfg(target_os = "linux")]
+pub fn set_address_space_limit(mb: u64) -> Result<(), String> {
+    let bytes = mb.checked_mul(1024 * 1024).ok_or_else(|| format!("{} MB is more than 64 bits of bytes", mb))? as libc::rlim_t;
+    let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrlimit(libc::RLIMIT_AS, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    // Only root may raise the hard limit, lowering it is fine
+    limit.rlim_cur = bytes;
+    limit.rlim_max = limit.rlim_max.min(bytes);
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_address_space_limit(_mb: u64) -> Result<(), String> {
+    Err("only supported on Linux".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn address_space_limit() -> Option<u64> {
+    let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrlimit(libc::RLIMIT_AS, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+    Some(limit.rlim_cur as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn address_space_limit() -> Option<u64> {
+    None
+}
+
+// Size of all mappings, the first field of /proc/self/statm, in pages
+#[cfg(target_os = "linux")]
+fn address_space_size() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().next()?.parse().ok()?;
+    Some(pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn address_space_size() -> Option<u64> {
+    None
+}
+
+// With cgroup v2, /proc/self/cgroup has a single line "0::/path". The limit
+// can be set on any ancestor (a container's cgroup, say), so the tightest one
+// on the way up is taken.
+#[cfg(target_os = "linux")]
+fn cgroup_limit() -> Option<(PathBuf, u64)> {
+    let root = Path::new("/sys/fs/cgroup");
+    let membership = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = membership.lines().find_map(|line| line.strip_prefix("0::"))?;
+    let own = root.join(path.trim_start_matches('/'));
+    own.ancestors()
+        .take_while(|cgroup| cgroup.starts_with(root) && *cgroup != root)
+        // "max" means no limit
+        .filter_map(|cgroup| read_number(&cgroup.join("memory.max")).map(|limit| (cgroup.to_path_buf(), limit)))
+        .min_by_key(|(_, limit)| *limit)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cgroup_limit() -> Option<(PathBuf, u64)> {
+    None
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
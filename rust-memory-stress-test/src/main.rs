@@ -1,7 +1,40 @@
 use raylib::prelude::*;
 use rand::prelude::*;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use memory_stats::memory_stats;
+
+mod allocator;
+mod frame_log;
+mod gc;
+mod limits;
+mod options;
+mod os_stats;
+mod payload;
+mod pressure;
+mod profiler;
+mod scenario;
+mod timing;
+mod trace;
+
+use rust_stress_common::{capture, clock, pacing, report, sampler};
+
+use capture::Capture;
+use clock::{ms, Clock, ClockCheck};
+use frame_log::{FrameLog, FrameRecord};
+use os_stats::OsStats;
+use sampler::{Gauge, Sampler};
+use gc::{GcHeap, GcMode};
+use limits::{LimitKind, MemoryLimit};
+use options::Options;
+use pressure::Pressure;
+use profiler::{scope, Profiler};
+use report::{RunRecorder, Tolerances};
+use scenario::{Action, Scenario};
+use pacing::Pacing;
+use payload::Payloads;
+use timing::FrameTimes;
+use trace::Tracer;
 
 struct Circle {
     x: f32,
@@ -11,26 +44,144 @@ struct Circle {
     color: Color,
 }
 
-fn get_memory_usage() -> f32 {
-    // Use memory-stats crate to get actual memory usage
-    if let Some(usage) = memory_stats() {
-        // Convert from bytes to MB
-        return usage.physical_mem as f32 / (1024.0 * 1024.0);
+// Where the stress objects live
+#[derive(Clone, Copy, PartialEq)]
+enum Workload {
+    // Vec<Vec<u8>>, freed as soon as they fall out of the retained window
+    Vec,
+    // Emulated tracing GC (see gc.rs), freed when the collector gets to them
+    Gc,
+}
+
+impl Workload {
+    fn name(&self) -> &'static str {
+        match self {
+            Workload::Vec => "vec",
+            Workload::Gc => "gc",
+        }
+    }
+}
+
+// Outcome of an explicit trim (M key / "trim" scenario step)
+struct TrimResult {
+    duration: Duration,
+    memory_before: f32,
+    memory_after: f32,
+    rebuilt: bool,
+    supported: bool,
+}
+
+// Why filling toward the memory limit stopped (F key / "fill" scenario step)
+struct FillStop {
+    reason: &'static str,
+    objects: usize,
+    memory_mb: f32,
+    headroom_mb: Option<f32>,
+}
+
+// Return free memory to the OS, optionally dropping and re-allocating all
+// retained objects first, and time the whole operation. With the GC
+// workload this runs a full collection first, like Racket's (collect-garbage).
+fn trim_memory(
+    objects: &mut Vec<Vec<u8>>,
+    heap: &mut GcHeap,
+    workload: Workload,
+    payloads: &mut Payloads,
+    rebuild: bool,
+) -> TrimResult {
+    let memory_before = sampler::read_memory_mb();
+    let start = Instant::now();
+    
+    if workload == Workload::Gc {
+        heap.collect();
+    }
+    let retained = objects.len();
+    if rebuild {
+        *objects = Vec::new();
+    }
+    let supported = allocator::trim();
+    if rebuild {
+        objects.extend((0..retained).map_while(|_| payloads.allocate()));
+    }
+    
+    let duration = start.elapsed();
+    TrimResult {
+        duration,
+        memory_before,
+        memory_after: sampler::read_memory_mb(),
+        rebuilt: rebuild,
+        supported,
     }
-    0.0
 }
 
-fn main() {
+fn main() -> ExitCode {
+    let options = Options::from_args();
     let (screen_width, screen_height) = (800, 600);
     
+    // In-process profiler when built with the puffin or tracy feature; it
+    // has to run before the first scope
+    let mut profiler = Profiler::start().unwrap_or_else(|e| {
+        eprintln!("Failed to start the profiler: {}", e);
+        std::process::exit(1);
+    });
+    
+    // Optional scripted run and baseline to check it against
+    let mut scenario = options.scenario_path.as_ref().map(|path| {
+        Scenario::load(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load scenario: {}", e);
+            std::process::exit(1);
+        })
+    });
+    let baseline = options.baseline_path.as_ref().map(|path| {
+        report::read_report(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load baseline: {}", e);
+            std::process::exit(1);
+        })
+    });
+    
+    // Optional per-frame log
+    let mut frame_log = options.log_path.as_ref().map(|path| {
+        FrameLog::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create log file {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    
+    // Frames are paced by our own limiter instead of raylib's set_target_fps,
+    // so the wait is timed apart from the buffer swap (see timing.rs)
+    let pacing = options.pacing;
+    println!("Pacing: {}", pacing.name());
+    
     // Initialize raylib - using proper tuple destructuring
-    let (mut rl_handle, thread) = raylib::init()
-        .size(screen_width, screen_height)
-        .title("Rust Raylib Performance Test")
-        .build();
+    let mut builder = raylib::init();
+    builder.size(screen_width, screen_height).title("Rust Raylib Performance Test");
+    if pacing == Pacing::Vsync {
+        builder.vsync();
+    }
+    let (mut rl_handle, thread) = builder.build();
+    
+    if options.headless {
+        rl_handle.set_window_state(WindowState::default().set_window_hidden(true));
+    }
     
-    // Set target FPS
-    rl_handle.set_target_fps(60);
+    // Limit the address space only now: the GL driver maps a lot of it while
+    // the window is created
+    if let Some(mb) = options.limit_as_mb {
+        if let Err(e) = limits::set_address_space_limit(mb) {
+            eprintln!("Failed to limit the address space to {} MB: {}", mb, e);
+            return ExitCode::FAILURE;
+        }
+    }
+    let memory_limit = MemoryLimit::detect();
+    let mut headroom_mb = memory_limit.as_ref().map(MemoryLimit::headroom_mb);
+    match (&memory_limit, headroom_mb) {
+        (Some(limit), Some(headroom)) => println!("Memory limit: {}, {:.1} MB headroom", limit.describe(), headroom),
+        _ => println!("Memory limit: none"),
+    }
+    if options.fill && memory_limit.is_none() {
+        eprintln!("--fill needs a memory limit (--limit-as-mb, ulimit -v or a cgroup memory.max)");
+        return ExitCode::FAILURE;
+    }
     
     // Memory stress test variables
     let mut stress_enabled = false;
@@ -39,21 +190,43 @@ fn main() {
     let objects_retained = 1000;
     let stress_objects_per_level = [100, 1000, 10000];
     let mut objects: Vec<Vec<u8>> = Vec::new();
+    let mut last_trim: Option<TrimResult> = None;
+    // Filling keeps every stress object until the limit is near
+    let mut fill = options.fill;
+    let mut fill_stop: Option<FillStop> = None;
+    let mut total_allocation_failures: u64 = 0;
+    
+    // Emulated GC heap, used instead of `objects` with the GC workload
+    let mut workload = options.workload;
+    let mut heap = GcHeap::new(
+        options.gc_mode,
+        (options.gc_threshold_mb * 1024.0 * 1024.0) as usize,
+        options.gc_slice,
+        objects_retained,
+    );
     
-    // Initialize circles
+    // Background allocator threads, running while Some
+    let mut pressure = options.background.then(|| Pressure::start(options.pressure));
+    let mut pressure_rate = 0.0;
+    let mut pressure_sample = (Instant::now(), 0);
+    
+    // Initialize circles from a seeded generator, so a run can be replayed
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
     let mut circles = Vec::new();
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut payloads = Payloads::new(options.payload, options.object_size, seed);
     
     for _ in 0..20 {
         circles.push(Circle {
-            x: rng.gen_range(0.0..800.0),
-            y: rng.gen_range(300.0..500.0),
-            radius: rng.gen_range(5.0..25.0),
-            speed: rng.gen_range(50.0..250.0),
+            x: rng.random_range(0.0..800.0),
+            y: rng.random_range(300.0..500.0),
+            radius: rng.random_range(5.0..25.0),
+            speed: rng.random_range(50.0..250.0),
             color: Color::new(
-                rng.gen_range(0..255),
-                rng.gen_range(0..255),
-                rng.gen_range(0..255),
+                rng.random_range(0..255),
+                rng.random_range(0..255),
+                rng.random_range(0..255),
                 255,
             ),
         });
@@ -62,28 +235,98 @@ fn main() {
     // Rotation angle
     let mut rotation = 0.0;
     
+    // Frame capture; its time is taken back out of the frame it happened in.
+    // The frames it holds on to count toward the memory readings.
+    let mut capture = if options.capture.enabled() {
+        // Frames come at the pacing's rate, or the display's with vsync
+        let fps = match pacing {
+            Pacing::Vsync => u32::try_from(get_monitor_refresh_rate(get_current_monitor())).ok().filter(|&hz| hz > 0),
+            _ => pacing.fps(),
+        };
+        let capture = Capture::start(options.capture, screen_width, screen_height, fps).unwrap_or_else(|e| {
+            eprintln!("Failed to start frame capture: {}", e);
+            std::process::exit(1);
+        });
+        println!("Capturing {}", capture.describe());
+        Some(capture)
+    } else {
+        None
+    };
+    
     // Performance measurement variables
     let mut frame_times = vec![0.0; 120];
     let mut frame_index = 0;
     let mut max_frame_time = 0.0;
     let mut last_max_reset_time = Instant::now();
     
-    // Timing measurement
-    let mut update_time = 0.0;
-    let mut render_time = 0.0;
-    let mut stress_time = 0.0;
+    // Timing measurement; the overlay can only show the last finished frame
+    let clock = Clock::new(options.clock);
+    println!("Clock: {}", clock.source.name());
+    let mut clock_check = ClockCheck::default();
+    let mut last_times = FrameTimes::default();
+    let mut os_stats = OsStats::new();
+    // The headroom takes a read of /proc or the cgroup too, so it is worked
+    // out on the sampler's thread along with smaps_rollup
+    let headroom = Arc::new(Gauge::default());
+    let sampler_hook = {
+        let mut read_smaps = os_stats.smaps_hook();
+        let headroom = Arc::clone(&headroom);
+        let memory_limit = memory_limit.clone();
+        move || {
+            read_smaps();
+            headroom.set(memory_limit.as_ref().map(MemoryLimit::headroom_mb));
+        }
+    };
+    let sampler = Sampler::start_with(options.sample_hz, Some(Box::new(sampler_hook)));
+    println!("Memory sampling: {}", sampler.describe());
+    // The last frame including its capture time, to compare with raylib's
+    let mut last_raw_ns = 0;
+    let mut last_stats_ns = 0;
+    let mut frame_start = clock.now();
+    let mut frame_number: u64 = 0;
+    
+    // Optional phase markers for profilers (see trace.rs)
+    let mut tracer = Tracer::new(options.trace_path.as_deref(), options.trace_marker, frame_start).unwrap_or_else(|e| {
+        eprintln!("Failed to set up tracing: {}", e);
+        std::process::exit(1);
+    });
+    tracer.begin("frame", frame_number, frame_start);
+    tracer.begin("update", frame_number, frame_start);
+    let run_start = Instant::now();
+    let mut recorder = RunRecorder::default();
+    // What the report says about the run, besides the phases
+    let mut report_metadata = vec![("binary", env!("CARGO_PKG_NAME").to_string())];
+    if let Some(scenario_path) = &options.scenario_path {
+        report_metadata.push(("scenario", scenario_path.display().to_string()));
+    }
+    report_metadata.push(("seed", seed.to_string()));
+    report_metadata.push(("stall_ms", options.stall_ms.to_string()));
+    report_metadata.push(("pacing", pacing.name()));
+    report_metadata.push(("clock", clock.source.name().to_string()));
+    report_metadata.push(("memory_sampling", sampler.describe()));
+    report_metadata.push(("memory_limit", memory_limit.as_ref().map_or("none".to_string(), MemoryLimit::describe)));
+    report_metadata.push(("background", options.pressure.describe()));
+    report_metadata.push(("payload", payloads.describe()));
+    report_metadata.push(("read_retained", options.read_retained.to_string()));
 
     // Main game loop
     while !rl_handle.window_should_close() {
-        let delta_time = rl_handle.get_frame_time();
+        profiler.new_frame();
         
-        // Record frame time
-        frame_times[frame_index] = delta_time;
-        frame_index = (frame_index + 1) % frame_times.len();
+        let elapsed = run_start.elapsed().as_secs_f64();
+        if scenario.as_ref().is_some_and(|s| s.finished(elapsed)) {
+            break;
+        }
         
-        if delta_time > max_frame_time {
-            max_frame_time = delta_time;
+        let delta_time = last_times.frame_ns as f32 / 1_000_000_000.0;
+        if frame_number > 0 {
+            clock_check.add(last_raw_ns, rl_handle.get_frame_time());
         }
+        let mut times = FrameTimes::default();
+        let mut capture_ns = 0;
+        // The OS counters read at the end of the last frame land in this one;
+        // like the capture, they don't count
+        let stats_ns = last_stats_ns;
         
         // Reset maximum frame time every 5 seconds
         if last_max_reset_time.elapsed() > Duration::from_secs(5) {
@@ -91,71 +334,301 @@ fn main() {
             max_frame_time = 0.0;
         }
         
-        // Update animations (with timing)
-        let update_start = Instant::now();
-        
-        // Update rotation
-        rotation += 90.0 * delta_time;
-        
-        // Update circle positions
-        for circle in &mut circles {
-            circle.x += circle.speed * delta_time;
-            if circle.x > screen_width as f32 + circle.radius {
-                circle.x = -circle.radius;
+        {
+            scope!("update_animations");
+            
+            // Update rotation
+            rotation += 90.0 * delta_time;
+            
+            // Update circle positions
+            for circle in &mut circles {
+                circle.x += circle.speed * delta_time;
+                if circle.x > screen_width as f32 + circle.radius {
+                    circle.x = -circle.radius;
+                }
             }
         }
-        update_time = update_start.elapsed().as_secs_f32();
         
-        // Process key inputs
+        // Scenario steps that are due, then key inputs
+        let mut actions = match &mut scenario {
+            Some(scenario) => scenario.due(elapsed),
+            None => Vec::new(),
+        };
         if rl_handle.is_key_pressed(KeyboardKey::KEY_G) {
-            stress_enabled = !stress_enabled;
+            actions.push(Action::ToggleStress);
         }
-        
         if rl_handle.is_key_pressed(KeyboardKey::KEY_ONE) {
-            stress_level = 1;
+            actions.push(Action::SetLevel(1));
         }
         if rl_handle.is_key_pressed(KeyboardKey::KEY_TWO) {
-            stress_level = 2;
+            actions.push(Action::SetLevel(2));
         }
         if rl_handle.is_key_pressed(KeyboardKey::KEY_THREE) {
-            stress_level = 3;
+            actions.push(Action::SetLevel(3));
         }
-        
         if rl_handle.is_key_pressed(KeyboardKey::KEY_R) {
-            objects.clear();
-            objects_created = 0;
+            actions.push(Action::Reset);
+        }
+        if rl_handle.is_key_pressed(KeyboardKey::KEY_M) {
+            let rebuild = rl_handle.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+                || rl_handle.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+            actions.push(Action::Trim { rebuild });
+        }
+        if rl_handle.is_key_pressed(KeyboardKey::KEY_W) {
+            actions.push(Action::ToggleWorkload);
         }
+        if rl_handle.is_key_pressed(KeyboardKey::KEY_C) {
+            actions.push(Action::ToggleGcMode);
+        }
+        if rl_handle.is_key_pressed(KeyboardKey::KEY_B) {
+            actions.push(Action::ToggleBackground);
+        }
+        if rl_handle.is_key_pressed(KeyboardKey::KEY_F) {
+            actions.push(Action::ToggleFill);
+        }
+        
+        for action in actions {
+            match action {
+                Action::SetStress(enabled) => stress_enabled = enabled,
+                Action::ToggleStress => stress_enabled = !stress_enabled,
+                Action::SetLevel(level) => stress_level = level,
+                Action::Reset => {
+                    objects.clear();
+                    heap.clear();
+                    objects_created = 0;
+                    fill_stop = None;
+                }
+                Action::SetWorkload(_) | Action::ToggleWorkload => {
+                    let new_workload = match action {
+                        Action::SetWorkload(w) => w,
+                        _ if workload == Workload::Vec => Workload::Gc,
+                        _ => Workload::Vec,
+                    };
+                    if new_workload != workload {
+                        // Start the new workload from an empty store
+                        objects.clear();
+                        heap.clear();
+                        workload = new_workload;
+                        println!("Workload: {}", workload.name());
+                        if workload == Workload::Gc && fill {
+                            fill = false;
+                            fill_stop = None;
+                            println!("Fill: off");
+                        }
+                    }
+                }
+                Action::SetGcMode(_) | Action::ToggleGcMode => {
+                    heap.mode = match action {
+                        Action::SetGcMode(mode) => mode,
+                        _ if heap.mode == GcMode::StopTheWorld => GcMode::Incremental,
+                        _ => GcMode::StopTheWorld,
+                    };
+                    println!("GC mode: {}", heap.mode.name());
+                }
+                Action::SetBackground(_) | Action::ToggleBackground => {
+                    let enabled = match action {
+                        Action::SetBackground(enabled) => enabled,
+                        _ => pressure.is_none(),
+                    };
+                    if enabled && pressure.is_none() {
+                        pressure = Some(Pressure::start(options.pressure));
+                        pressure_sample = (Instant::now(), 0);
+                        pressure_rate = 0.0;
+                        println!("Background pressure: {}", options.pressure.describe());
+                    } else if !enabled && pressure.is_some() {
+                        // Dropping stops and joins the threads
+                        pressure = None;
+                        println!("Background pressure: off");
+                    }
+                }
+                Action::SetFill(_) | Action::ToggleFill => {
+                    let enabled = match action {
+                        Action::SetFill(enabled) => enabled,
+                        _ => !fill,
+                    };
+                    if enabled && workload == Workload::Gc {
+                        // The GC heap keeps only its newest objects rooted
+                        println!("Fill: only with the Vec workload");
+                    } else if enabled && memory_limit.is_none() {
+                        println!("Fill: needs a memory limit");
+                    } else if enabled != fill {
+                        fill = enabled;
+                        fill_stop = None;
+                        if !fill {
+                            // Back to the usual number of retained objects,
+                            // the newest ones as when stress runs without fill
+                            objects.drain(..objects.len().saturating_sub(objects_retained));
+                        }
+                        println!("Fill: {}", if fill { "on" } else { "off" });
+                    }
+                }
+                Action::Trim { rebuild } => {
+                    let result = trim_memory(&mut objects, &mut heap, workload, &mut payloads, rebuild);
+                    println!("Trim ({}{}): {:.2} ms, {:.2} MB -> {:.2} MB",
+                             allocator::name(),
+                             if rebuild { ", objects rebuilt" } else { "" },
+                             result.duration.as_secs_f32() * 1000.0,
+                             result.memory_before,
+                             result.memory_after);
+                    last_trim = Some(result);
+                }
+            }
+        }
+        
+        // Everything up to here (animations, input, scenario steps) is the update
+        let stress_start = clock.now();
+        times.update_ns = stress_start.ns_since(frame_start) - stats_ns;
+        tracer.end("update", frame_number, stress_start);
         
         // Memory stress test (with timing)
-        let stress_start = Instant::now();
+        tracer.begin("stress", frame_number, stress_start);
+        // Stress objects the allocator couldn't provide this frame; a failure
+        // switches stress off, but the frame still counts as a stress one
+        let mut allocation_failures = 0;
+        let stressed = stress_enabled;
         if stress_enabled {
+            scope!("stress_allocations");
             let objects_per_frame = stress_objects_per_level[stress_level - 1];
-            for _ in 0..objects_per_frame {
-                objects.push(vec![0; 1000]);
-                objects_created += 1;
+            match workload {
+                Workload::Vec if fill => {
+                    // Grow toward the limit. Under a cgroup limit nothing fails,
+                    // the OOM killer strikes, so stop at the margin instead.
+                    // Either way the stop is expected and handled here, not
+                    // counted as an allocation failure.
+                    let near_limit = memory_limit.as_ref().is_some_and(|limit| limit.kind == LimitKind::Cgroup)
+                        && headroom_mb.is_some_and(|headroom| headroom < options.fill_margin_mb);
+                    let mut full = false;
+                    if !near_limit {
+                        for _ in 0..objects_per_frame {
+                            match payloads.allocate() {
+                                Some(object) if objects.try_reserve(1).is_ok() => {
+                                    objects.push(object);
+                                    objects_created += 1;
+                                }
+                                _ => {
+                                    full = true;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    if near_limit || full {
+                        // Give a frame's worth back, so the rest of the
+                        // program has something to allocate from
+                        objects.truncate(objects.len().saturating_sub(objects_per_frame));
+                        let stop = FillStop {
+                            reason: if near_limit { "margin reached" } else { "allocation failed" },
+                            objects: objects.len(),
+                            memory_mb: sampler.memory_mb(),
+                            headroom_mb,
+                        };
+                        println!("Fill stopped ({}) at {} objects, {:.2} MB, {:.1} MB headroom",
+                                 stop.reason,
+                                 stop.objects,
+                                 stop.memory_mb,
+                                 stop.headroom_mb.unwrap_or(0.0));
+                        fill_stop = Some(stop);
+                        stress_enabled = false;
+                    }
+                }
+                Workload::Vec => {
+                    for _ in 0..objects_per_frame {
+                        match payloads.allocate() {
+                            Some(object) if objects.try_reserve(1).is_ok() => {
+                                objects.push(object);
+                                objects_created += 1;
+                            }
+                            _ => {
+                                allocation_failures += 1;
+                                break;
+                            }
+                        }
+                    }
+                    
+                    // Limit the number of objects
+                    if objects.len() > objects_retained {
+                        objects.drain(0..(objects.len() - objects_retained));
+                    }
+                }
+                Workload::Gc => {
+                    // The heap keeps only the newest objects rooted
+                    for _ in 0..objects_per_frame {
+                        if payloads.allocate().is_none_or(|object| heap.allocate(object).is_err()) {
+                            allocation_failures += 1;
+                            break;
+                        }
+                        objects_created += 1;
+                    }
+                }
             }
-            
-            // Limit the number of objects
-            if objects.len() > objects_retained {
-                objects.drain(0..(objects.len() - objects_retained));
+        }
+        // Walk the retained objects like a program reading its data
+        if options.read_retained {
+            scope!("read_retained");
+            match workload {
+                Workload::Vec => payload::read_all(objects.iter().map(Vec::as_slice)),
+                Workload::Gc => payload::read_all(heap.rooted_payloads()),
+            };
+        }
+        // An incremental collection keeps going whether or not stress is on
+        heap.step();
+        let stress_end = clock.now();
+        times.stress_ns = stress_end.ns_since(stress_start);
+        tracer.end("stress", frame_number, stress_end);
+        
+        // Out of memory: switch the stress off before something that can't
+        // fail gracefully runs out as well, and get what was measured so far
+        // onto disk in case that happens anyway
+        if allocation_failures > 0 {
+            total_allocation_failures += allocation_failures;
+            stress_enabled = false;
+            println!("Allocation failed at stress level {} ({:.2} MB), stress off",
+                     stress_level,
+                     sampler.memory_mb());
+            if let Some(log) = &mut frame_log {
+                if let Err(e) = log.flush() {
+                    eprintln!("Failed to write frame log: {}", e);
+                    frame_log = None;
+                }
+            }
+            if let Some(path) = &options.report_path {
+                let mut metadata = report_metadata.clone();
+                metadata.push(("allocation_failures", total_allocation_failures.to_string()));
+                match report::write_report(path, &metadata, &recorder.summarize(options.stall_ms)) {
+                    Ok(()) => println!("Report so far written to {}", path.display()),
+                    Err(e) => eprintln!("Failed to write report {}: {}", path.display(), e),
+                }
+            }
+        }
+        
+        // Measured background allocation rate, updated once a second
+        if let Some(pressure) = &pressure {
+            let (since, count) = pressure_sample;
+            if since.elapsed() >= Duration::from_secs(1) {
+                let allocations = pressure.allocations();
+                pressure_rate = (allocations - count) as f64 / since.elapsed().as_secs_f64();
+                pressure_sample = (Instant::now(), allocations);
             }
         }
-        stress_time = stress_start.elapsed().as_secs_f32();
         
-        // Drawing (with timing)
-        let render_start = Instant::now();
+        // Drawing (with timing); this only submits the draw calls
+        let draw_start = clock.now();
+        tracer.begin("draw", frame_number, draw_start);
         let mut d = rl_handle.begin_drawing(&thread);
         
         d.clear_background(Color::WHITE);
         
         // Draw circles
-        for circle in &circles {
-            d.draw_circle(
-                circle.x as i32,
-                circle.y as i32,
-                circle.radius,
-                circle.color,
-            );
+        {
+            scope!("draw_circles");
+            for circle in &circles {
+                d.draw_circle(
+                    circle.x as i32,
+                    circle.y as i32,
+                    circle.radius,
+                    circle.color,
+                );
+            }
         }
         
         // Draw rotating rectangle
@@ -169,10 +642,61 @@ fn main() {
         );
         
         // Display information
-        let current_memory = get_memory_usage();
+        let current_memory = sampler.memory_mb();
         d.draw_text(&format!("Memory Usage: {:.2} MB", current_memory), 20, 20, 20, Color::BLACK);
+        d.draw_text(&format!("Faults: {} minor, {} major  Switches: {} vol, {} invol",
+                             os_stats.frame.minor_faults,
+                             os_stats.frame.major_faults,
+                             os_stats.frame.voluntary_switches,
+                             os_stats.frame.involuntary_switches),
+                    300, 22, 16, Color::DARKGRAY);
+        if let Some(smaps) = &os_stats.smaps {
+            d.draw_text(&format!("RSS {:.1}, PSS {:.1}, Anon {:.1}, Swap {:.1} MB",
+                                 smaps.rss_mb, smaps.pss_mb, smaps.anonymous_mb, smaps.swap_mb),
+                        300, 52, 16, Color::DARKGRAY);
+        }
         d.draw_text(&format!("FPS: {}", d.get_fps()), 20, 50, 20, Color::BLACK);
-        d.draw_text(&format!("Max Frame Time: {:.2} ms", max_frame_time * 1000.0), 20, 80, 20, Color::BLACK);
+        let mut recent = frame_times.clone();
+        recent.sort_by(f32::total_cmp);
+        d.draw_text(&format!("Max Frame Time: {:.2} ms (last 2 s: p50 {:.2}, p99 {:.2})",
+                             max_frame_time * 1000.0,
+                             recent[recent.len() / 2] * 1000.0,
+                             recent[recent.len() * 99 / 100] * 1000.0),
+                    20, 80, 20, Color::BLACK);
+        
+        // Display the last explicit trim
+        let trim_text = match &last_trim {
+            Some(trim) if !trim.supported => format!("not supported by the {} allocator", allocator::name()),
+            Some(trim) => format!("{:.2} ms, {:.2} -> {:.2} MB ({}{})",
+                                  trim.duration.as_secs_f32() * 1000.0,
+                                  trim.memory_before,
+                                  trim.memory_after,
+                                  allocator::name(),
+                                  if trim.rebuilt { ", rebuilt" } else { "" }),
+            None => "-".to_string(),
+        };
+        d.draw_text(&format!("Last Trim: {}", trim_text), 20, 110, 20, Color::DARKBLUE);
+        
+        // Display the workload and, for the GC heap, its collector
+        match workload {
+            Workload::Vec => {
+                d.draw_text("Workload: Vec<Vec<u8>>", 20, 140, 20, Color::DARKBLUE);
+            }
+            Workload::Gc => {
+                d.draw_text(&format!("Workload: GC heap ({}, threshold {:.1} MB)",
+                                     heap.mode.name(),
+                                     heap.threshold as f32 / (1024.0 * 1024.0)),
+                            20, 140, 20, Color::DARKBLUE);
+                d.draw_text(&format!("GC: {} collections{}, pause {:.2} ms (max {:.2}), heap {:.2} MB",
+                                     heap.stats.collections,
+                                     if heap.collecting() { " +1 running" } else { "" },
+                                     heap.stats.last_pause.as_secs_f32() * 1000.0,
+                                     heap.stats.max_pause.as_secs_f32() * 1000.0,
+                                     heap.live_bytes() as f32 / (1024.0 * 1024.0)),
+                            20, 170, 20,
+                            if heap.collecting() { Color::RED } else { Color::DARKBLUE });
+            }
+        }
         
         // Display memory stress status
         let stress_text = if stress_enabled {
@@ -182,23 +706,216 @@ fn main() {
         };
         d.draw_text(&format!("Memory Stress: {}", stress_text), 20, 210, 20, 
                     if stress_enabled { Color::RED } else { Color::GREEN });
-        d.draw_text(&format!("Objects Created: {}", objects_created), 20, 240, 20, Color::BLACK);
-        d.draw_text(&format!("Objects Retained: {}", objects.len()), 20, 270, 20, Color::BLACK);
+        if total_allocation_failures > 0 {
+            d.draw_text(&format!("Objects Created: {} ({} failed)", objects_created, total_allocation_failures),
+                        20, 240, 20, Color::RED);
+        } else {
+            d.draw_text(&format!("Objects Created: {}", objects_created), 20, 240, 20, Color::BLACK);
+        }
+        
+        // Display the memory limit and how filling toward it went
+        if let (Some(limit), Some(headroom)) = (&memory_limit, headroom_mb) {
+            d.draw_text(&format!("Limit: {}, {:.1} MB left", limit.describe(), headroom),
+                        440, 212, 18, if headroom < options.fill_margin_mb { Color::RED } else { Color::DARKBLUE });
+            let fill_text = match &fill_stop {
+                Some(stop) => format!("stopped, {} at {:.1} MB", stop.reason, stop.memory_mb),
+                None if fill => "ON".to_string(),
+                None => "OFF".to_string(),
+            };
+            d.draw_text(&format!("Fill: {}", fill_text), 440, 242, 18,
+                        if fill_stop.is_some() { Color::RED } else { Color::DARKBLUE });
+        }
+        match workload {
+            Workload::Vec => {
+                d.draw_text(&format!("Objects Retained: {}", objects.len()), 20, 270, 20, Color::BLACK);
+            }
+            Workload::Gc => {
+                d.draw_text(&format!("Objects Retained: {} (live in heap: {})", heap.rooted(), heap.live_objects()),
+                            20, 270, 20, Color::BLACK);
+            }
+        }
         
-        // Display performance measurements
-        d.draw_text(&format!("Update Time: {:.2} ms", update_time * 1000.0), 20, 500, 18, Color::DARKGRAY);
-        d.draw_text(&format!("Render Time: {:.2} ms", render_time * 1000.0), 20, 530, 18, Color::DARKGRAY);
-        d.draw_text(&format!("Stress Test Time: {:.2} ms", stress_time * 1000.0), 20, 560, 18, Color::DARKGRAY);
+        // Display the background threads and what they actually manage to do
+        match &pressure {
+            Some(pressure) => {
+                let failures = match pressure.failures() {
+                    0 => String::new(),
+                    failures => format!(", {} failed", failures),
+                };
+                d.draw_text(&format!("Background: {} - {:.0}k allocs/s{}",
+                                     pressure.config.describe(),
+                                     pressure_rate / 1000.0,
+                                     failures),
+                            20, 300, 20, Color::RED);
+            }
+            None => d.draw_text("Background: OFF", 20, 300, 20, Color::GREEN),
+        }
+        
+        // Display where the last frame's time went
+        d.draw_text(&format!("Last Frame: {:.3} ms (pacing: {}, clock: {}, raylib drift: {:.2} ms)",
+                             ms(last_times.frame_ns),
+                             pacing.name(),
+                             clock.source.name(),
+                             clock_check.drift_ms()),
+                    20, 510, 18, Color::DARKGRAY);
+        d.draw_text(&format!("Update: {:.3} ms, Stress: {:.3} ms, Draw: {:.3} ms",
+                             ms(last_times.update_ns),
+                             ms(last_times.stress_ns),
+                             ms(last_times.draw_ns)),
+                    20, 535, 18, Color::DARKGRAY);
+        let sampling = sampler.cost();
+        d.draw_text(&format!("Swap: {:.3} ms, Idle Wait: {:.3} ms, Memory Sampling: {}, {:.1} us ({:.2}% CPU)",
+                             ms(last_times.swap_ns),
+                             ms(last_times.wait_ns),
+                             sampler.describe(),
+                             sampling.mean_us,
+                             sampling.percent),
+                    20, 560, 18, Color::DARKGRAY);
         
         // Instructions
         d.draw_text("Instructions:", 20, 340, 20, Color::DARKGRAY);
-        d.draw_text("- G: Toggle memory stress test", 40, 370, 18, Color::DARKGRAY);
-        d.draw_text("- 1/2/3: Select stress level (low/medium/high)", 40, 400, 18, Color::DARKGRAY);
-        d.draw_text("- R: Reset metrics", 40, 430, 18, Color::DARKGRAY);
-        d.draw_text("- ESC: Exit", 40, 460, 18, Color::DARKGRAY);
+        d.draw_text(&format!("Stress Objects: {}{}",
+                             payloads.describe(),
+                             if options.read_retained { ", read every frame" } else { "" }),
+                    300, 342, 18, Color::DARKBLUE);
+        d.draw_text("- G: Toggle memory stress test", 40, 365, 18, Color::DARKGRAY);
+        d.draw_text("- 1/2/3: Select stress level (low/medium/high)", 40, 387, 18, Color::DARKGRAY);
+        d.draw_text("- M: Trim allocator / full GC (Shift+M: also rebuild objects)", 40, 409, 18, Color::DARKGRAY);
+        d.draw_text("- W: Toggle workload (Vec / GC heap), C: Toggle GC mode", 40, 431, 18, Color::DARKGRAY);
+        d.draw_text("- B: Toggle background allocator threads, F: Fill to the limit, R: Reset metrics", 40, 453, 18, Color::DARKGRAY);
+        d.draw_text("- ESC: Exit", 40, 475, 18, Color::DARKGRAY);
         
-        // Don't need to explicitly end drawing with newer raylib bindings
+        // Capture the finished frame, overlay included
+        if let Some(capture) = &mut capture {
+            let previous_stalled = ms(last_times.frame_ns) > options.stall_ms;
+            let capture_start = clock.now();
+            tracer.begin("capture", frame_number, capture_start);
+            capture.frame(&mut d, &thread, frame_number, previous_stalled);
+            let capture_end = clock.now();
+            capture_ns = capture_end.ns_since(capture_start);
+            tracer.end("capture", frame_number, capture_end);
+        }
         
-        render_time = render_start.elapsed().as_secs_f32();
+        // Dropping the handle ends the frame: the batch is flushed, the
+        // buffers are swapped and the input is polled
+        let swap_start = clock.now();
+        times.draw_ns = swap_start.ns_since(draw_start) - capture_ns;
+        tracer.end("draw", frame_number, swap_start);
+        tracer.begin("swap", frame_number, swap_start);
+        {
+            scope!("end_drawing");
+            drop(d);
+        }
+        let swap_end = clock.now();
+        times.swap_ns = swap_end.ns_since(swap_start);
+        tracer.end("swap", frame_number, swap_end);
+        
+        // Idle until the frame's slot is over; the capture doesn't count
+        tracer.begin("wait", frame_number, swap_end);
+        times.wait_ns = pacing.wait(&clock, frame_start, capture_ns + stats_ns);
+        let frame_end = clock.now();
+        tracer.end("wait", frame_number, frame_end);
+        tracer.end("frame", frame_number, frame_end);
+        last_raw_ns = frame_end.ns_since(frame_start);
+        times.frame_ns = last_raw_ns - capture_ns - stats_ns;
+        frame_start = frame_end;
+        last_times = times;
+        os_stats.sample();
+        headroom_mb = headroom.get();
+        last_stats_ns = clock.ns_since(frame_end);
+        
+        // The next frame's update starts with recording this one
+        tracer.begin("frame", frame_number + 1, frame_start);
+        tracer.begin("update", frame_number + 1, frame_start);
+        
+        // Record frame time
+        let frame_time = times.frame_ns as f32 / 1_000_000_000.0;
+        frame_times[frame_index] = frame_time;
+        frame_index = (frame_index + 1) % frame_times.len();
+        
+        if frame_time > max_frame_time {
+            max_frame_time = frame_time;
+        }
+        
+        // Record this frame
+        let mut phase = if stressed {
+            format!("stress-{}", stress_level)
+        } else {
+            "idle".to_string()
+        };
+        if workload == Workload::Gc {
+            phase = format!("{}+gc-{}", phase, heap.mode.name());
+        }
+        if pressure.is_some() {
+            phase.push_str("+bg");
+        }
+        if fill && workload == Workload::Vec {
+            phase.push_str("+fill");
+        }
+        recorder.record(&phase, ms(times.frame_ns), current_memory);
+        if let Some(log) = &mut frame_log {
+            let record = FrameRecord {
+                frame: frame_number,
+                time_ms: run_start.elapsed().as_secs_f64() * 1000.0,
+                phase: &phase,
+                times,
+                memory_mb: current_memory,
+                os: os_stats.frame,
+                smaps: os_stats.smaps,
+                headroom_mb,
+                allocation_failures,
+            };
+            if let Err(e) = log.record(&record) {
+                eprintln!("Failed to write frame log: {}", e);
+                frame_log = None;
+            }
+        }
+        frame_number += 1;
     }
+    
+    // Stop the background threads and close the window before reporting
+    drop(pressure);
+    drop(rl_handle);
+    drop(tracer);
+    let sampling = sampler.cost();
+    
+    println!("Clock check: {:.3} ms drift from raylib's frame times, at most {:.3} ms in a frame",
+             clock_check.drift_ms(),
+             clock_check.max_frame_ms);
+    println!("Memory sampling: {} samples, {:.1} us each (max {:.1} us), {:.3}% of a core",
+             sampling.samples,
+             sampling.mean_us,
+             sampling.max_us,
+             sampling.percent);
+    
+    // Waits for ffmpeg to finish the video
+    if let Some(capture) = capture {
+        println!("Captured {} frames", capture.saved);
+    }
+    
+    let summaries = recorder.summarize(options.stall_ms);
+    if let Some(path) = &options.report_path {
+        let mut metadata = report_metadata;
+        metadata.push(("allocation_failures", total_allocation_failures.to_string()));
+        match report::write_report(path, &metadata, &summaries) {
+            Ok(()) => println!("Report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write report {}: {}", path.display(), e),
+        }
+    }
+    
+    if let Some(baseline) = &baseline {
+        let tolerances = Tolerances {
+            frame_percent: options.frame_tolerance,
+            stalls: options.stall_tolerance,
+            memory_percent: options.memory_tolerance,
+        };
+        let regressions = report::compare_with_baseline(baseline, &summaries, &tolerances);
+        if regressions > 0 {
+            eprintln!("{} regression(s) against the baseline", regressions);
+            return ExitCode::FAILURE;
+        }
+        println!("No regressions against the baseline");
+    }
+    
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,285 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::capture::CaptureConfig;
+use crate::clock::ClockSource;
+use crate::gc::GcMode;
+use crate::pacing::Pacing;
+use crate::sampler;
+use crate::payload::Payload;
+use crate::pressure::{Pattern, PressureConfig};
+use crate::Workload;
+
+// Command line options
+pub struct Options {
+    // Per-frame CSV log (see frame_log.rs for the columns)
+    pub log_path: Option<PathBuf>,
+    // Scripted run (see scenario.rs for the format)
+    pub scenario_path: Option<PathBuf>,
+    pub headless: bool,
+    // Per-phase summary written when the run ends
+    pub report_path: Option<PathBuf>,
+    // Report of an earlier run to check this one against
+    pub baseline_path: Option<PathBuf>,
+    pub stall_ms: f32,
+    pub frame_tolerance: f32,
+    pub stall_tolerance: usize,
+    pub memory_tolerance: f32,
+    // Seed for the scene's random placement; random when not given
+    pub seed: Option<u64>,
+    pub workload: Workload,
+    pub gc_mode: GcMode,
+    pub gc_threshold_mb: f32,
+    pub gc_slice: usize,
+    // How stress objects are initialized, and their size (see payload.rs)
+    pub payload: Payload,
+    pub object_size: usize,
+    // Read every retained object each frame
+    pub read_retained: bool,
+    // Background allocator pressure, started with --background or the B key
+    pub background: bool,
+    pub pressure: PressureConfig,
+    // Frames to save as PNGs or a video (see capture.rs)
+    pub capture: CaptureConfig,
+    pub pacing: Pacing,
+    // Where frame and phase times come from (see clock.rs)
+    pub clock: ClockSource,
+    // Memory samples per second off the render thread, 0 = every frame on it
+    pub sample_hz: u32,
+    // Address space limit to set at startup (see limits.rs)
+    pub limit_as_mb: Option<u64>,
+    // Keep stress objects until the memory limit is near instead of 1000
+    pub fill: bool,
+    // Headroom at which filling stops under a cgroup limit
+    pub fill_margin_mb: f32,
+    // Frame phase markers as Chrome trace JSON and to ftrace (see trace.rs)
+    pub trace_path: Option<PathBuf>,
+    pub trace_marker: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            log_path: None,
+            scenario_path: None,
+            headless: false,
+            report_path: None,
+            baseline_path: None,
+            stall_ms: 1000.0 / 30.0,
+            frame_tolerance: 10.0,
+            stall_tolerance: 0,
+            memory_tolerance: 10.0,
+            seed: None,
+            workload: Workload::Vec,
+            gc_mode: GcMode::StopTheWorld,
+            gc_threshold_mb: 8.0,
+            gc_slice: 20_000,
+            payload: Payload::Zero,
+            object_size: 1000,
+            read_retained: false,
+            background: false,
+            pressure: PressureConfig {
+                threads: 4,
+                rate: 100_000,
+                size: 1000,
+                retained: 10_000,
+                pattern: Pattern::Fifo,
+            },
+            capture: CaptureConfig {
+                every: None,
+                stalls: false,
+                window: 30,
+                dir: PathBuf::from("capture"),
+                video: None,
+            },
+            pacing: Pacing::Fixed(60),
+            clock: ClockSource::Instant,
+            sample_hz: 100,
+            limit_as_mb: None,
+            fill: false,
+            fill_margin_mb: 64.0,
+            trace_path: None,
+            trace_marker: false,
+        }
+    }
+}
+
+const USAGE: &str = "\
+Usage: rust-raylib-memory-stress-test [OPTIONS]
+
+Options:
+  --log <FILE>               Write a per-frame CSV log to FILE
+  --scenario <FILE>          Drive the run from a scenario script and exit at its end
+  --headless                 Keep the window hidden (needs --scenario)
+  --report <FILE>            Write a per-phase summary to FILE when the run ends
+  --baseline <FILE>          Compare the run against an earlier report; exit 1 on regression
+  --stall-ms <MS>            Frame time counted as a stall (default: 33.3)
+  --frame-tolerance <PCT>    Allowed p50/p90/p99 increase over the baseline (default: 10)
+  --stall-tolerance <N>      Allowed extra stalls per phase over the baseline (default: 0)
+  --memory-tolerance <PCT>   Allowed peak memory increase over the baseline (default: 10)
+  --seed <N>                 Seed the scene's random number generator to replay a run
+  --workload <vec|gc>        Keep stress objects in Vecs or in the emulated GC heap (default: vec)
+  --gc-mode <stw|incremental>
+                             Collect in one pause or in per-frame slices (default: stw)
+  --gc-threshold-mb <MB>     Allocation between collections (default: 8)
+  --gc-slice <N>             Objects marked or swept per incremental slice (default: 20000)
+  --payload <zero|fill|random|touch>
+                             How stress objects are initialized (default: zero)
+  --object-size <BYTES>      Size of each stress object (default: 1000)
+  --read-retained            Read every retained stress object each frame
+  --background               Start with the background allocator threads running
+  --bg-threads <N>           Number of background threads (default: 4)
+  --bg-rate <N>              Allocations per second per thread, 0 = unthrottled (default: 100000)
+  --bg-size <BYTES>          Size of each background allocation (default: 1000)
+  --bg-retained <N>          Objects each background thread holds on to (default: 10000)
+  --bg-pattern <fifo|random|burst>
+                             Order in which background objects are freed (default: fifo)
+  --capture-every <N>        Save every Nth frame
+  --capture-stalls           Save the frames around each stall
+  --capture-window <N>       Frames saved before and after a stall (default: 30)
+  --capture-dir <DIR>        Directory for the captured PNGs (default: capture)
+  --capture-video <FILE>     Pipe the captured frames to ffmpeg instead (default: every frame)
+  --pacing <uncapped|fixed:FPS|vsync|busy:FPS>
+                             How frames are paced (default: fixed:60)
+  --clock <instant|tsc>      Clock for frame and phase times (default: instant)
+  --sample-hz <N>            Memory samples per second up to 10000, 0 = inline every frame (default: 100)
+  --limit-as-mb <MB>         Limit the address space to MB (setrlimit RLIMIT_AS)
+  --fill                     Keep every stress object, filling memory toward the limit (vec workload)
+  --fill-margin-mb <MB>      Headroom at which filling stops under a cgroup limit (default: 64)
+  --trace <FILE>             Write every frame phase to FILE as Chrome trace JSON (for Perfetto)
+  --trace-marker             Write frame phase markers to ftrace's trace_marker (for perf)
+  -h, --help                 Print this help";
+
+impl Options {
+    pub fn from_args() -> Options {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--log" => options.log_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--scenario" => options.scenario_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--headless" => options.headless = true,
+                "--report" => options.report_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--baseline" => options.baseline_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--stall-ms" => options.stall_ms = parse_value(&arg, args.next()),
+                "--frame-tolerance" => options.frame_tolerance = parse_value(&arg, args.next()),
+                "--stall-tolerance" => options.stall_tolerance = parse_value(&arg, args.next()),
+                "--memory-tolerance" => options.memory_tolerance = parse_value(&arg, args.next()),
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())),
+                "--workload" => {
+                    options.workload = match value_of(&arg, args.next()).as_str() {
+                        "vec" => Workload::Vec,
+                        "gc" => Workload::Gc,
+                        _ => usage_error("--workload must be vec or gc"),
+                    }
+                }
+                "--gc-mode" => {
+                    options.gc_mode = match value_of(&arg, args.next()).as_str() {
+                        "stw" => GcMode::StopTheWorld,
+                        "incremental" => GcMode::Incremental,
+                        _ => usage_error("--gc-mode must be stw or incremental"),
+                    }
+                }
+                "--gc-threshold-mb" => options.gc_threshold_mb = parse_value(&arg, args.next()),
+                "--gc-slice" => options.gc_slice = parse_value(&arg, args.next()),
+                "--payload" => {
+                    options.payload = Payload::parse(&value_of(&arg, args.next()))
+                        .unwrap_or_else(|| usage_error("--payload must be zero, fill, random or touch"))
+                }
+                "--object-size" => options.object_size = parse_value(&arg, args.next()),
+                "--read-retained" => options.read_retained = true,
+                "--background" => options.background = true,
+                "--bg-threads" => options.pressure.threads = parse_value(&arg, args.next()),
+                "--bg-rate" => options.pressure.rate = parse_value(&arg, args.next()),
+                "--bg-size" => options.pressure.size = parse_value(&arg, args.next()),
+                "--bg-retained" => options.pressure.retained = parse_value(&arg, args.next()),
+                "--bg-pattern" => {
+                    options.pressure.pattern = match value_of(&arg, args.next()).as_str() {
+                        "fifo" => Pattern::Fifo,
+                        "random" => Pattern::Random,
+                        "burst" => Pattern::Burst,
+                        _ => usage_error("--bg-pattern must be fifo, random or burst"),
+                    }
+                }
+                "--capture-every" => {
+                    let every = parse_value(&arg, args.next());
+                    if every == 0 {
+                        usage_error("--capture-every must be at least 1");
+                    }
+                    options.capture.every = Some(every);
+                }
+                "--capture-stalls" => options.capture.stalls = true,
+                "--capture-window" => options.capture.window = parse_value(&arg, args.next()),
+                "--capture-dir" => options.capture.dir = PathBuf::from(value_of(&arg, args.next())),
+                "--capture-video" => options.capture.video = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--clock" => {
+                    options.clock = match value_of(&arg, args.next()).as_str() {
+                        "instant" => ClockSource::Instant,
+                        "tsc" => ClockSource::Tsc,
+                        _ => usage_error("--clock must be instant or tsc"),
+                    }
+                }
+                "--trace" => options.trace_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--trace-marker" => options.trace_marker = true,
+                "--limit-as-mb" => {
+                    let mb: u64 = parse_value(&arg, args.next());
+                    if mb.checked_mul(1024 * 1024).is_none() {
+                        usage_error("--limit-as-mb is too large");
+                    }
+                    options.limit_as_mb = Some(mb);
+                }
+                "--fill" => options.fill = true,
+                "--fill-margin-mb" => options.fill_margin_mb = parse_value(&arg, args.next()),
+                "--sample-hz" => {
+                    options.sample_hz = parse_value(&arg, args.next());
+                    if options.sample_hz > sampler::MAX_HZ {
+                        usage_error(&format!("--sample-hz must be at most {}", sampler::MAX_HZ));
+                    }
+                }
+                "--pacing" => {
+                    options.pacing = Pacing::parse(&value_of(&arg, args.next())).unwrap_or_else(|| {
+                        usage_error("--pacing must be uncapped, fixed:<fps>, vsync or busy:<fps>")
+                    })
+                }
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => usage_error(&format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if options.headless && options.scenario_path.is_none() {
+            usage_error("--headless needs --scenario");
+        }
+        if options.fill && options.workload == Workload::Gc {
+            usage_error("--fill needs --workload vec");
+        }
+        if options.capture.video.is_some() && !options.capture.enabled() {
+            options.capture.every = Some(1);
+        }
+
+        options
+    }
+}
+
+fn value_of(flag: &str, value: Option<String>) -> String {
+    match value {
+        Some(value) => value,
+        None => usage_error(&format!("{} needs a value", flag)),
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value_of(flag, value);
+    match value.parse() {
+        Ok(parsed) => parsed,
+        Err(_) => usage_error(&format!("invalid value '{}' for {}", value, flag)),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    std::process::exit(2);
+}
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sampler::Gauge;
+
+// What the OS did for (or to) the process each frame. Page faults are the
+// usual suspect for allocation spikes: memory fresh from mmap is only backed
+// on first touch, one minor fault per page, and a major fault means waiting
+// for the disk. Context switches tell apart blocking (voluntary) from being
+// preempted (involuntary). Only read on Linux; elsewhere the counters stay at
+// zero and there are no smaps figures.
+
+// getrusage counters; cumulative, or the difference over a frame
+#[derive(Clone, Copy, Default)]
+pub struct OsCounters {
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+}
+
+impl OsCounters {
+    #[cfg(target_os = "linux")]
+    fn read() -> OsCounters {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+            return OsCounters::default();
+        }
+        OsCounters {
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+            voluntary_switches: usage.ru_nvcsw as u64,
+            involuntary_switches: usage.ru_nivcsw as u64,
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read() -> OsCounters {
+        OsCounters::default()
+    }
+
+    fn since(&self, earlier: &OsCounters) -> OsCounters {
+        OsCounters {
+            minor_faults: self.minor_faults.saturating_sub(earlier.minor_faults),
+            major_faults: self.major_faults.saturating_sub(earlier.major_faults),
+            voluntary_switches: self.voluntary_switches.saturating_sub(earlier.voluntary_switches),
+            involuntary_switches: self.involuntary_switches.saturating_sub(earlier.involuntary_switches),
+        }
+    }
+}
+
+// Figures from /proc/self/smaps_rollup, in MB
+#[derive(Clone, Copy, Default)]
+pub struct Smaps {
+    pub rss_mb: f32,
+    pub pss_mb: f32,
+    pub anonymous_mb: f32,
+    pub swap_mb: f32,
+}
+
+// smaps_rollup walks every mapping of the process, which costs more the more
+// memory there is, so it is read by the memory sampler's thread (see
+// smaps_hook), a few times a second, and the render loop only loads the
+// latest figures
+const SMAPS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Default)]
+struct SmapsGauges {
+    rss_mb: Gauge,
+    pss_mb: Gauge,
+    anonymous_mb: Gauge,
+    swap_mb: Gauge,
+}
+
+impl SmapsGauges {
+    fn set(&self, smaps: Option<Smaps>) {
+        self.rss_mb.set(smaps.map(|smaps| smaps.rss_mb));
+        self.pss_mb.set(smaps.map(|smaps| smaps.pss_mb));
+        self.anonymous_mb.set(smaps.map(|smaps| smaps.anonymous_mb));
+        self.swap_mb.set(smaps.map(|smaps| smaps.swap_mb));
+    }
+
+    fn get(&self) -> Option<Smaps> {
+        Some(Smaps {
+            rss_mb: self.rss_mb.get()?,
+            pss_mb: self.pss_mb.get()?,
+            anonymous_mb: self.anonymous_mb.get()?,
+            swap_mb: self.swap_mb.get()?,
+        })
+    }
+}
+
+pub struct OsStats {
+    total: OsCounters,
+    // The last frame's counts
+    pub frame: OsCounters,
+    // None where there is no smaps_rollup (not Linux, or a kernel before 4.14)
+    pub smaps: Option<Smaps>,
+    published: Arc<SmapsGauges>,
+}
+
+impl OsStats {
+    pub fn new() -> OsStats {
+        OsStats {
+            total: OsCounters::read(),
+            frame: OsCounters::default(),
+            smaps: None,
+            published: Arc::default(),
+        }
+    }
+
+    // For the memory sampler: reads smaps_rollup when it is due
+    pub fn smaps_hook(&self) -> impl FnMut() + Send + 'static {
+        let published = Arc::clone(&self.published);
+        let mut last_read: Option<Instant> = None;
+        move || {
+            if last_read.is_none_or(|read| read.elapsed() >= SMAPS_INTERVAL) {
+                last_read = Some(Instant::now());
+                published.set(read_smaps());
+            }
+        }
+    }
+
+    // Call once per frame, at its end. Only a getrusage call and a few
+    // atomic loads.
+    pub fn sample(&mut self) {
+        let total = OsCounters::read();
+        self.frame = total.since(&self.total);
+        self.total = total;
+        self.smaps = self.published.get();
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_smaps() -> Option<Smaps> {
+    std::fs::read_to_string("/proc/self/smaps_rollup").ok().map(|text| parse_smaps(&text))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_smaps() -> Option<Smaps> {
+    None
+}
+
+// Lines look like "Rss:              123456 kB"
+#[cfg(target_os = "linux")]
+fn parse_smaps(text: &str) -> Smaps {
+    let mut smaps = Smaps::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let kb: f32 = match value.trim().trim_end_matches("kB").trim().parse() {
+            Ok(kb) => kb,
+            Err(_) => continue,
+        };
+        let mb = kb / 1024.0;
+        match key {
+            "Rss" => smaps.rss_mb = mb,
+            "Pss" => smaps.pss_mb = mb,
+            "Anonymous" => smaps.anonymous_mb = mb,
+            "Swap" => smaps.swap_mb = mb,
+            _ => {}
+        }
+    }
+    smaps
+}
@@ -0,0 +1,131 @@
+use std::alloc::{self, Layout};
+use std::hint::black_box;
+
+// What goes into a stress object. A zeroed allocation is what `vec![0; n]`
+// does: it goes through alloc_zeroed, and memory fresh from the OS is zero
+// already, so its pages may never be touched and never become resident.
+// Racket's make-vector initializes every slot, so to put the same pressure on
+// the memory system the other modes write to the object.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Payload {
+    // Zeroed, possibly never touched
+    Zero,
+    // Every byte written with a non-zero value
+    Fill,
+    // Every byte random, so nothing can be deduplicated or compressed
+    Random,
+    // Zeroed, then one byte written per page
+    Touch,
+}
+
+impl Payload {
+    pub fn parse(text: &str) -> Option<Payload> {
+        match text {
+            "zero" => Some(Payload::Zero),
+            "fill" => Some(Payload::Fill),
+            "random" => Some(Payload::Random),
+            "touch" => Some(Payload::Touch),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Payload::Zero => "zero",
+            Payload::Fill => "fill",
+            Payload::Random => "random",
+            Payload::Touch => "touch",
+        }
+    }
+}
+
+const PAGE_SIZE: usize = 4096;
+const CACHE_LINE: usize = 64;
+
+// Makes the stress objects
+pub struct Payloads {
+    pub mode: Payload,
+    pub size: usize,
+    random_state: u64,
+}
+
+impl Payloads {
+    pub fn new(mode: Payload, size: usize, seed: u64) -> Payloads {
+        // xorshift gets stuck at 0
+        Payloads { mode, size, random_state: seed | 1 }
+    }
+
+    // A new object, or None when the allocator can't provide one
+    pub fn allocate(&mut self) -> Option<Vec<u8>> {
+        match self.mode {
+            Payload::Zero => allocate_zeroed(self.size),
+            Payload::Fill => {
+                let mut object = Vec::new();
+                object.try_reserve_exact(self.size).ok()?;
+                object.resize(self.size, 0xa5);
+                Some(object)
+            }
+            Payload::Random => {
+                let mut object = Vec::new();
+                object.try_reserve_exact(self.size).ok()?;
+                while object.len() < self.size {
+                    let bytes = self.next_random().to_le_bytes();
+                    let take = bytes.len().min(self.size - object.len());
+                    object.extend_from_slice(&bytes[..take]);
+                }
+                Some(object)
+            }
+            Payload::Touch => {
+                let mut object = allocate_zeroed(self.size)?;
+                for i in (0..object.len()).step_by(PAGE_SIZE) {
+                    object[i] = 1;
+                }
+                Some(object)
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} B {}", self.size, self.mode.name())
+    }
+
+    fn next_random(&mut self) -> u64 {
+        let mut x = self.random_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.random_state = x;
+        x
+    }
+}
+
+// vec![0; size], but returning None instead of aborting
+pub fn allocate_zeroed(size: usize) -> Option<Vec<u8>> {
+    if size == 0 {
+        return Some(Vec::new());
+    }
+    let layout = Layout::array::<u8>(size).ok()?;
+    let ptr = unsafe { alloc::alloc_zeroed(layout) };
+    if ptr.is_null() {
+        return None;
+    }
+    // Allocated by the global allocator with the layout of `size` bytes,
+    // which is what the Vec frees it with
+    Some(unsafe { Vec::from_raw_parts(ptr, size, size) })
+}
+
+// Read one byte per cache line of every object, the way a program walks its
+// data each frame. Returns the number of bytes read.
+pub fn read_all<'a>(objects: impl Iterator<Item = &'a [u8]>) -> usize {
+    let mut read = 0;
+    let mut sum = 0u8;
+    for object in objects {
+        for i in (0..object.len()).step_by(CACHE_LINE) {
+            sum = sum.wrapping_add(object[i]);
+        }
+        read += object.len();
+    }
+    black_box(sum);
+    read
+}
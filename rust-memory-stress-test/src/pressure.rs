@@ -0,0 +1,170 @@
+use rand::prelude::*;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::payload;
+
+// Background threads that allocate and free while the render thread keeps
+// drawing, to see what allocator contention from other threads does to the
+// frame times.
+
+// In which order a thread frees what it allocated
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pattern {
+    // Keep the newest `retained` objects, free the oldest
+    Fifo,
+    // Random sizes, and a random victim once `retained` objects are held
+    Random,
+    // Hold up to `retained` objects, then free them all at once
+    Burst,
+}
+
+impl Pattern {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::Fifo => "fifo",
+            Pattern::Random => "random",
+            Pattern::Burst => "burst",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PressureConfig {
+    pub threads: usize,
+    // Allocations per second per thread, 0 = as fast as possible
+    pub rate: u64,
+    // Object size in bytes (the average one with the random pattern)
+    pub size: usize,
+    // Objects each thread holds on to
+    pub retained: usize,
+    pub pattern: Pattern,
+}
+
+impl PressureConfig {
+    pub fn describe(&self) -> String {
+        let rate = if self.rate == 0 { "unthrottled".to_string() } else { format!("{}/s each", self.rate) };
+        format!("{} threads, {}, {} B, {}", self.threads, self.pattern.name(), self.size, rate)
+    }
+}
+
+// Running background threads; they are stopped and joined on drop
+pub struct Pressure {
+    pub config: PressureConfig,
+    stop: Arc<AtomicBool>,
+    allocations: Arc<AtomicU64>,
+    failures: Arc<AtomicU64>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Pressure {
+    pub fn start(config: PressureConfig) -> Pressure {
+        let stop = Arc::new(AtomicBool::new(false));
+        let allocations = Arc::new(AtomicU64::new(0));
+        let failures = Arc::new(AtomicU64::new(0));
+        let handles = (0..config.threads)
+            .map(|i| {
+                let stop = Arc::clone(&stop);
+                let allocations = Arc::clone(&allocations);
+                let failures = Arc::clone(&failures);
+                thread::Builder::new()
+                    .name(format!("pressure-{}", i))
+                    .spawn(move || run(config, &stop, &allocations, &failures))
+                    .expect("failed to spawn background pressure thread")
+            })
+            .collect();
+        Pressure { config, stop, allocations, failures, handles }
+    }
+
+    // Allocations done by all threads so far
+    pub fn allocations(&self) -> u64 {
+        self.allocations.load(Ordering::Relaxed)
+    }
+
+    // Allocations the threads couldn't get, each followed by a back-off
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Pressure {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Work is done in 1 ms ticks so that the rate holds without one big burst per second
+const TICK: Duration = Duration::from_millis(1);
+// How long a thread pauses after an allocation failed
+const BACKOFF: Duration = Duration::from_millis(100);
+
+fn run(config: PressureConfig, stop: &AtomicBool, allocations: &AtomicU64, failures: &AtomicU64) {
+    let mut rng = rand::rng();
+    // Reserve the queue up front without aborting on a huge --bg-retained;
+    // if that much can't be had it just grows as objects come in
+    let mut held: VecDeque<Vec<u8>> = VecDeque::new();
+    if held.try_reserve(config.retained.saturating_add(1)).is_err() {
+        failures.fetch_add(1, Ordering::Relaxed);
+    }
+    let per_tick = config.rate as f64 * TICK.as_secs_f64();
+    let mut owed = 0.0;
+    let mut next_tick = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        let count = if config.rate == 0 {
+            256
+        } else {
+            owed += per_tick;
+            let count = owed as usize;
+            owed -= count as f64;
+            count
+        };
+
+        let mut done = 0;
+        for _ in 0..count {
+            let size = match config.pattern {
+                Pattern::Random => rng.random_range(config.size.min(16)..=config.size * 2),
+                _ => config.size,
+            };
+            let Some(object) = payload::allocate_zeroed(size) else {
+                // Out of memory (under a memory limit, say): let go of half of
+                // what is held and leave the render thread some room for a while
+                failures.fetch_add(1, Ordering::Relaxed);
+                held.drain(..held.len() / 2);
+                thread::sleep(BACKOFF);
+                break;
+            };
+            held.push_back(object);
+            done += 1;
+
+            if held.len() > config.retained {
+                match config.pattern {
+                    Pattern::Fifo => drop(held.pop_front()),
+                    Pattern::Random => {
+                        let victim = rng.random_range(0..held.len());
+                        drop(held.swap_remove_back(victim));
+                    }
+                    Pattern::Burst => held.clear(),
+                }
+            }
+        }
+        allocations.fetch_add(done, Ordering::Relaxed);
+
+        if config.rate > 0 {
+            next_tick += TICK;
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            } else {
+                // Fell behind; don't try to catch up in one go
+                next_tick = now;
+            }
+        }
+    }
+}
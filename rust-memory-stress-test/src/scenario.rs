@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+
+use crate::gc::GcMode;
+use crate::Workload;
+
+// Something a scenario step (or a key press) asks the stress test to do
+#[derive(Clone, Copy)]
+pub enum Action {
+    SetStress(bool),
+    ToggleStress,
+    SetLevel(usize),
+    Reset,
+    Trim { rebuild: bool },
+    SetWorkload(Workload),
+    ToggleWorkload,
+    SetGcMode(GcMode),
+    ToggleGcMode,
+    SetBackground(bool),
+    ToggleBackground,
+    SetFill(bool),
+    ToggleFill,
+}
+
+// A scripted run, one step per line:
+//
+//   # comment
+//   wait 3        # let this much time pass (seconds)
+//   stress on     # on | off
+//   level 3       # 1 | 2 | 3
+//   reset
+//   trim          # return free memory to the OS (M key)
+//   trim rebuild  # drop and re-allocate all objects, then trim (Shift+M)
+//   workload gc   # vec | gc
+//   gc-mode incremental  # stw | incremental
+//   background on # on | off, allocator threads (B key)
+//   fill on       # on | off, fill toward the memory limit (F key)
+//
+// The run ends once the last step has been reached.
+pub struct Scenario {
+    steps: Vec<(f64, Action)>,
+    end_time: f64,
+    next: usize,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Scenario::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Scenario, String> {
+        let mut steps = Vec::new();
+        let mut time = 0.0;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+
+            match words.as_slice() {
+                [] => {}
+                ["wait", seconds] => {
+                    // A NaN would keep the run from ever finishing
+                    let seconds = match seconds.parse::<f64>() {
+                        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => seconds,
+                        _ => return Err(error("wait needs a number of seconds, 0 or more")),
+                    };
+                    time += seconds;
+                }
+                ["stress", "on"] => steps.push((time, Action::SetStress(true))),
+                ["stress", "off"] => steps.push((time, Action::SetStress(false))),
+                ["level", level] => match level.parse() {
+                    Ok(level @ 1..=3) => steps.push((time, Action::SetLevel(level))),
+                    _ => return Err(error("level must be 1, 2 or 3")),
+                },
+                ["reset"] => steps.push((time, Action::Reset)),
+                ["trim"] => steps.push((time, Action::Trim { rebuild: false })),
+                ["trim", "rebuild"] => steps.push((time, Action::Trim { rebuild: true })),
+                ["workload", "vec"] => steps.push((time, Action::SetWorkload(Workload::Vec))),
+                ["workload", "gc"] => steps.push((time, Action::SetWorkload(Workload::Gc))),
+                ["gc-mode", "stw"] => steps.push((time, Action::SetGcMode(GcMode::StopTheWorld))),
+                ["gc-mode", "incremental"] => steps.push((time, Action::SetGcMode(GcMode::Incremental))),
+                ["background", "on"] => steps.push((time, Action::SetBackground(true))),
+                ["background", "off"] => steps.push((time, Action::SetBackground(false))),
+                ["fill", "on"] => steps.push((time, Action::SetFill(true))),
+                ["fill", "off"] => steps.push((time, Action::SetFill(false))),
+                _ => return Err(error(&format!("unknown step '{}'", line))),
+            }
+        }
+
+        Ok(Scenario { steps, end_time: time, next: 0 })
+    }
+
+    // Actions that have come due by `elapsed` seconds into the run
+    pub fn due(&mut self, elapsed: f64) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Some(&(time, action)) = self.steps.get(self.next) {
+            if time > elapsed {
+                break;
+            }
+            actions.push(action);
+            self.next += 1;
+        }
+        actions
+    }
+
+    pub fn finished(&self, elapsed: f64) -> bool {
+        self.next == self.steps.len() && elapsed >= self.end_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_come_due_after_their_waits() {
+        let mut scenario = Scenario::parse("stress on\nwait 2  # warm up\nlevel 3\n\nwait 1.5\nstress off\n").unwrap();
+        assert!(matches!(scenario.due(0.0).as_slice(), [Action::SetStress(true)]));
+        assert!(scenario.due(1.9).is_empty());
+        assert!(matches!(scenario.due(2.0).as_slice(), [Action::SetLevel(3)]));
+        assert!(!scenario.finished(3.4));
+        assert!(matches!(scenario.due(3.5).as_slice(), [Action::SetStress(false)]));
+        assert!(scenario.finished(3.5));
+    }
+
+    #[test]
+    fn a_trailing_wait_holds_the_end() {
+        let mut scenario = Scenario::parse("trim rebuild\nwait 5\n").unwrap();
+        assert!(matches!(scenario.due(0.0).as_slice(), [Action::Trim { rebuild: true }]));
+        assert!(!scenario.finished(4.0));
+        assert!(scenario.finished(5.0));
+    }
+
+    #[test]
+    fn bad_steps_name_their_line() {
+        assert_eq!(Scenario::parse("stress on\nlevel 4").err().unwrap(), "line 2: level must be 1, 2 or 3");
+        assert_eq!(Scenario::parse("jump").err().unwrap(), "line 1: unknown step 'jump'");
+        assert!(Scenario::parse("fill maybe").is_err());
+    }
+
+    #[test]
+    fn waits_must_be_finite_and_not_negative() {
+        for wait in ["wait -1", "wait NaN", "wait inf", "wait soon"] {
+            assert!(Scenario::parse(wait).is_err(), "{}", wait);
+        }
+        assert!(Scenario::parse("wait 0").is_ok());
+    }
+}
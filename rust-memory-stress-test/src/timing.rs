@@ -0,0 +1,18 @@
+// Where the time of one frame went. raylib's own limiter waits inside
+// EndDrawing, together with the buffer swap, so a frame spent waiting for the
+// next tick and a frame spent in a stall both just show up as frame time.
+// Ending the frame and waiting out the budget (see pacing.rs) are therefore
+// done by the main loop, each timed on its own.
+
+// Nanoseconds per phase of a frame (see clock.rs); frame_ns covers the whole
+// frame, from the end of the previous one's wait to the end of its own, less
+// any frame capture
+#[derive(Clone, Copy, Default)]
+pub struct FrameTimes {
+    pub update_ns: u64,
+    pub stress_ns: u64,
+    pub draw_ns: u64,
+    pub swap_ns: u64,
+    pub wait_ns: u64,
+    pub frame_ns: u64,
+}
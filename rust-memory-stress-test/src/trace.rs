@@ -0,0 +1,100 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::clock::Tick;
+
+// Phase markers for profilers. Every frame phase can go to a Chrome
+// trace-event JSON file (open it in Perfetto or chrome://tracing) and to
+// ftrace's trace_marker, where `perf record -e ftrace:print` or trace-cmd pick
+// them up next to the samples.
+
+// Where the kernel takes the markers; the second path is for older mounts
+const TRACE_MARKER_PATHS: [&str; 2] = ["/sys/kernel/tracing/trace_marker", "/sys/kernel/debug/tracing/trace_marker"];
+
+pub struct Tracer {
+    json: Option<BufWriter<File>>,
+    marker: Option<File>,
+    // Trace timestamps count from here
+    origin: Tick,
+    pid: u32,
+}
+
+impl Tracer {
+    pub fn new(json_path: Option<&Path>, trace_marker: bool, origin: Tick) -> io::Result<Tracer> {
+        let pid = std::process::id();
+        let json = match json_path {
+            Some(path) => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                // Events are written with the comma in front, so the file
+                // always ends in a complete one
+                write!(
+                    writer,
+                    r#"[{{"name":"thread_name","ph":"M","pid":{},"tid":1,"args":{{"name":"main"}}}}"#,
+                    pid
+                )?;
+                Some(writer)
+            }
+            None => None,
+        };
+        let marker = if trace_marker {
+            let opened = TRACE_MARKER_PATHS.iter().find_map(|path| OpenOptions::new().write(true).open(path).ok());
+            match opened {
+                Some(file) => Some(file),
+                None => {
+                    return Err(io::Error::other(
+                        "can't open trace_marker (needs tracefs mounted and write access)",
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+        Ok(Tracer { json, marker, origin, pid })
+    }
+
+    pub fn begin(&mut self, phase: &str, frame: u64, at: Tick) {
+        self.event(phase, 'B', frame, at);
+    }
+
+    pub fn end(&mut self, phase: &str, frame: u64, at: Tick) {
+        self.event(phase, 'E', frame, at);
+    }
+
+    fn event(&mut self, phase: &str, kind: char, frame: u64, at: Tick) {
+        if let Some(marker) = &mut self.marker {
+            // One write per marker, so the kernel takes it as one event
+            let line = format!("stress-test: {} {} frame={}\n", kind, phase, frame);
+            if let Err(e) = marker.write_all(line.as_bytes()) {
+                eprintln!("Failed to write trace_marker: {}", e);
+                self.marker = None;
+            }
+        }
+        if let Some(json) = &mut self.json {
+            let result = write!(
+                json,
+                r#",
+{{"name":"{}","ph":"{}","ts":{:.3},"pid":{},"tid":1,"args":{{"frame":{}}}}}"#,
+                phase,
+                kind,
+                at.ns_since(self.origin) as f64 / 1000.0,
+                self.pid,
+                frame,
+            );
+            if let Err(e) = result {
+                eprintln!("Failed to write trace: {}", e);
+                self.json = None;
+            }
+        }
+    }
+}
+
+impl Drop for Tracer {
+    // Trace viewers also take a file without the closing `]`, from a run
+    // that didn't get here
+    fn drop(&mut self) {
+        if let Some(json) = &mut self.json {
+            let _ = writeln!(json, "\n]");
+        }
+    }
+}
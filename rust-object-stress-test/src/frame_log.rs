@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::postfx::PassTimes;
+
+// One row of the per-frame log.
+// The Racket script writes the same phase names, frame_ms and memory_mb
+// columns, so rust-compare-results can line both runs up.
+pub struct FrameRecord<'a> {
+    pub frame: u64,
+    pub time_ms: f64,
+    pub phase: &'a str,
+    pub frame_ms: f32,
+    pub processing_ms: f32,
+    pub physics_ms: f32,
+    // Time spent adding/removing objects during the frame in frame_ms
+    pub resize_ms: f32,
+    // Post-processing passes, all zero without render to texture
+    pub post: PassTimes,
+    pub memory_mb: f32,
+}
+
+pub struct FrameLog {
+    writer: BufWriter<File>,
+}
+
+impl FrameLog {
+    pub fn create(path: &Path) -> io::Result<FrameLog> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,time_ms,phase,frame_ms,processing_ms,physics_ms,resize_ms,blur_submit_ms,bloom_submit_ms,grade_submit_ms,present_submit_ms,memory_mb")?;
+        Ok(FrameLog { writer })
+    }
+
+    pub fn record(&mut self, record: &FrameRecord) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{:.3},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.2}",
+            record.frame,
+            record.time_ms,
+            record.phase,
+            record.frame_ms,
+            record.processing_ms,
+            record.physics_ms,
+            record.resize_ms,
+            record.post.blur_submit_ms,
+            record.post.bloom_submit_ms,
+            record.post.grade_submit_ms,
+            record.post.present_submit_ms,
+            record.memory_mb,
+        )
+    }
+}
@@ -0,0 +1,200 @@
+use raylib::prelude::*;
+
+// Optional lit path for the 3D objects: Blinn-Phong shading from up to
+// MAX_LIGHTS lights, evaluated either per vertex (Gouraud) or per pixel. The
+// unlit path keeps raylib's default shader.
+
+pub const MAX_LIGHTS: usize = 4;
+
+// How the 3D objects are shaded
+#[derive(Clone, Copy, PartialEq)]
+pub enum Shading {
+    Unlit,
+    Vertex,
+    Pixel,
+}
+
+impl Shading {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shading::Unlit => "unlit",
+            Shading::Vertex => "vertex",
+            Shading::Pixel => "pixel",
+        }
+    }
+}
+
+// A directional light shines from `position` towards the origin; a point
+// light fades with distance (kind as in the shader's lightKind)
+struct Light {
+    kind: i32,
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+// The first `--lights` of these are switched on
+const LIGHTS: [Light; MAX_LIGHTS] = [
+    Light { kind: 0, position: [-1.0, 2.0, 1.0], color: [0.9, 0.85, 0.8] },
+    Light { kind: 1, position: [8.0, 4.0, 8.0], color: [0.9, 0.3, 0.2] },
+    Light { kind: 1, position: [-8.0, 4.0, -8.0], color: [0.2, 0.4, 0.9] },
+    Light { kind: 1, position: [0.0, 8.0, 0.0], color: [0.3, 0.9, 0.3] },
+];
+
+const AMBIENT: f32 = 0.2;
+
+// Shared by both shaders; returns the factor the base color is multiplied
+// with, so black wireframes stay black
+const LIGHTING: &str = "
+#define MAX_LIGHTS 4
+uniform int lightCount;
+uniform int lightKind[MAX_LIGHTS];
+uniform vec3 lightPosition[MAX_LIGHTS];
+uniform vec3 lightColor[MAX_LIGHTS];
+uniform vec3 viewPos;
+uniform float ambient;
+
+vec3 lighting(vec3 position, vec3 normal)
+{
+    vec3 n = normalize(normal);
+    vec3 viewDir = normalize(viewPos - position);
+    vec3 light = vec3(ambient);
+    for (int i = 0; i < lightCount; i++) {
+        vec3 toLight = lightPosition[i];
+        float attenuation = 1.0;
+        if (lightKind[i] == 1) {
+            toLight = lightPosition[i] - position;
+            attenuation = 1.0 / (1.0 + 0.01 * dot(toLight, toLight));
+        }
+        vec3 l = normalize(toLight);
+        float diffuse = max(dot(n, l), 0.0);
+        float specular = 0.0;
+        if (diffuse > 0.0) {
+            specular = pow(max(dot(n, normalize(l + viewDir)), 0.0), 32.0);
+        }
+        light += lightColor[i] * (diffuse + specular) * attenuation;
+    }
+    return light;
+}
+";
+
+const VERTEX_LIT_VS: &str = "#version 330
+in vec3 vertexPosition;
+in vec3 vertexNormal;
+in vec4 vertexColor;
+uniform mat4 mvp;
+uniform mat4 matModel;
+uniform mat4 matNormal;
+out vec4 fragColor;
+LIGHTING
+void main()
+{
+    vec3 position = vec3(matModel * vec4(vertexPosition, 1.0));
+    vec3 normal = vec3(matNormal * vec4(vertexNormal, 0.0));
+    fragColor = vec4(vertexColor.rgb * lighting(position, normal), vertexColor.a);
+    gl_Position = mvp * vec4(vertexPosition, 1.0);
+}
+";
+
+const VERTEX_LIT_FS: &str = "#version 330
+in vec4 fragColor;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+void main()
+{
+    finalColor = colDiffuse * fragColor;
+}
+";
+
+const PIXEL_LIT_VS: &str = "#version 330
+in vec3 vertexPosition;
+in vec3 vertexNormal;
+in vec4 vertexColor;
+uniform mat4 mvp;
+uniform mat4 matModel;
+uniform mat4 matNormal;
+out vec3 fragPosition;
+out vec3 fragNormal;
+out vec4 fragColor;
+void main()
+{
+    fragPosition = vec3(matModel * vec4(vertexPosition, 1.0));
+    fragNormal = vec3(matNormal * vec4(vertexNormal, 0.0));
+    fragColor = vertexColor;
+    gl_Position = mvp * vec4(vertexPosition, 1.0);
+}
+";
+
+const PIXEL_LIT_FS: &str = "#version 330
+in vec3 fragPosition;
+in vec3 fragNormal;
+in vec4 fragColor;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+LIGHTING
+void main()
+{
+    vec4 base = colDiffuse * fragColor;
+    finalColor = vec4(base.rgb * lighting(fragPosition, fragNormal), base.a);
+}
+";
+
+pub struct Lighting {
+    vertex: Shader,
+    pixel: Shader,
+    pub light_count: usize,
+}
+
+impl Lighting {
+    pub fn load(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        light_count: usize,
+        view_position: Vector3,
+    ) -> Result<Lighting, String> {
+        let mut vertex = rl.load_shader_from_memory(
+            thread,
+            Some(&VERTEX_LIT_VS.replace("LIGHTING", LIGHTING)),
+            Some(VERTEX_LIT_FS),
+        );
+        let mut pixel = rl.load_shader_from_memory(
+            thread,
+            Some(PIXEL_LIT_VS),
+            Some(&PIXEL_LIT_FS.replace("LIGHTING", LIGHTING)),
+        );
+
+        // The lights and the camera don't move, so the uniforms are set once
+        for (name, shader) in [("per-vertex", &mut vertex), ("per-pixel", &mut pixel)] {
+            if !shader.is_shader_valid() {
+                return Err(format!("the {} lighting shader failed to load", name));
+            }
+            let lights = &LIGHTS[..light_count];
+            let kinds: Vec<i32> = lights.iter().map(|light| light.kind).collect();
+            let positions: Vec<[f32; 3]> = lights.iter().map(|light| light.position).collect();
+            let colors: Vec<[f32; 3]> = lights.iter().map(|light| light.color).collect();
+
+            let location = shader.get_shader_location("lightCount");
+            shader.set_shader_value(location, light_count as i32);
+            let location = shader.get_shader_location("lightKind");
+            shader.set_shader_value_v(location, &kinds);
+            let location = shader.get_shader_location("lightPosition");
+            shader.set_shader_value_v(location, &positions);
+            let location = shader.get_shader_location("lightColor");
+            shader.set_shader_value_v(location, &colors);
+            let location = shader.get_shader_location("viewPos");
+            shader.set_shader_value(location, view_position);
+            let location = shader.get_shader_location("ambient");
+            shader.set_shader_value(location, AMBIENT);
+        }
+
+        Ok(Lighting { vertex, pixel, light_count })
+    }
+
+    // The shader for `shading`; None means raylib's default one
+    pub fn shader(&mut self, shading: Shading) -> Option<&mut Shader> {
+        match shading {
+            Shading::Unlit => None,
+            Shading::Vertex => Some(&mut self.vertex),
+            Shading::Pixel => Some(&mut self.pixel),
+        }
+    }
+}
@@ -1,7 +1,40 @@
 use raylib::prelude::*;
 use rand::prelude::*;
+use std::process::ExitCode;
 use std::time::{Duration, Instant};
-use memory_stats::memory_stats;
+
+mod frame_log;
+mod lighting;
+mod models;
+mod options;
+mod physics;
+mod postfx;
+mod profiler;
+mod ramp;
+mod scenario;
+
+use rust_stress_common::{capture, clock, pacing, report, sampler};
+
+use capture::Capture;
+use clock::{ms, Clock, ClockCheck};
+use frame_log::{FrameLog, FrameRecord};
+use lighting::{Lighting, Shading};
+use models::{ShapeModel, ShapeModels};
+use options::Options;
+use pacing::Pacing;
+use physics::{Physics, PhysicsStats};
+use postfx::{Pass, PassTimes, PostFx, PostPasses};
+use profiler::{scope, Profiler};
+use ramp::Ramp;
+use report::{RunRecorder, Tolerances};
+use sampler::Sampler;
+use scenario::{Action, Scenario};
+
+// Upper bound on the object count, so a typo can't allocate gigabytes
+const MAX_OBJECTS: i32 = 1_000_000;
+
+// Counts on F1..F6
+const COUNT_PRESETS: [i32; 6] = [100, 1_000, 7_500, 10_000, 50_000, 250_000];
 
 // 2D objects
 struct Circle {
@@ -9,6 +42,8 @@ struct Circle {
     y: f32,
     radius: f32,
     speed: f32,
+    // Vertical velocity, only used by the physics mode
+    vy: f32,
     color: Color,
 }
 
@@ -18,10 +53,11 @@ struct Rectangle2D {
     width: f32,
     height: f32,
     speed: f32,
+    vy: f32,
     color: Color,
 }
 
-// 3D objects
+// 3D objects; every 3D shape type uses the same placement, only the mesh differs
 struct Cube {
     position: Vector3,
     size: Vector3,
@@ -29,42 +65,213 @@ struct Cube {
     color: Color,
 }
 
-// Shape type enum
+// Shape type enum; the first three are 2D shapes, the rest 3D (see models.rs)
+#[derive(Clone, Copy, PartialEq)]
 enum ShapeType {
     Circle,
     Rectangle,
     Mixed,
+    Cube,
+    Sphere,
+    Cylinder,
+    Plane,
+    Model,
+}
+
+impl ShapeType {
+    fn name(&self) -> &'static str {
+        match self {
+            ShapeType::Circle => "circle",
+            ShapeType::Rectangle => "rectangle",
+            ShapeType::Mixed => "mixed",
+            ShapeType::Cube => "cube",
+            ShapeType::Sphere => "sphere",
+            ShapeType::Cylinder => "cylinder",
+            ShapeType::Plane => "plane",
+            ShapeType::Model => "model",
+        }
+    }
+    
+    // The render mode that draws this shape type
+    fn mode(&self) -> RenderMode {
+        match self {
+            ShapeType::Circle | ShapeType::Rectangle | ShapeType::Mixed => RenderMode::Mode2D,
+            _ => RenderMode::Mode3D,
+        }
+    }
+    
+    // The shape type a switch to `mode` starts with
+    fn first(mode: RenderMode) -> ShapeType {
+        match mode {
+            RenderMode::Mode2D => ShapeType::Circle,
+            RenderMode::Mode3D => ShapeType::Cube,
+        }
+    }
+    
+    // The next shape type of the same mode (S key)
+    fn next(&self) -> ShapeType {
+        match self {
+            ShapeType::Circle => ShapeType::Rectangle,
+            ShapeType::Rectangle => ShapeType::Mixed,
+            ShapeType::Mixed => ShapeType::Circle,
+            ShapeType::Cube => ShapeType::Sphere,
+            ShapeType::Sphere => ShapeType::Cylinder,
+            ShapeType::Cylinder => ShapeType::Plane,
+            ShapeType::Plane => ShapeType::Model,
+            ShapeType::Model => ShapeType::Cube,
+        }
+    }
 }
 
 // Render mode enum
+#[derive(Clone, Copy, PartialEq)]
 enum RenderMode {
     Mode2D,
     Mode3D,
 }
 
-fn get_memory_usage() -> f32 {
-    // Use memory-stats crate to get actual memory usage
-    if let Some(usage) = memory_stats() {
-        // Convert from bytes to MB
-        return usage.physical_mem as f32 / (1024.0 * 1024.0);
+impl RenderMode {
+    fn name(&self) -> &'static str {
+        match self {
+            RenderMode::Mode2D => "2d",
+            RenderMode::Mode3D => "3d",
+        }
     }
-    0.0
 }
 
-fn main() {
+// How objects are blended into the frame
+#[derive(Clone, Copy)]
+enum Blend {
+    Opaque,
+    Alpha,
+    Additive,
+}
+
+impl Blend {
+    fn name(&self) -> &'static str {
+        match self {
+            Blend::Opaque => "opaque",
+            Blend::Alpha => "alpha",
+            Blend::Additive => "additive",
+        }
+    }
+    
+    // Alpha the objects are drawn with
+    fn alpha(&self) -> u8 {
+        match self {
+            Blend::Opaque => 255,
+            Blend::Alpha => 128,
+            Blend::Additive => 64,
+        }
+    }
+    
+    fn mode(&self) -> BlendMode {
+        match self {
+            Blend::Additive => BlendMode::BLEND_ADDITIVE,
+            _ => BlendMode::BLEND_ALPHA,
+        }
+    }
+}
+
+// Overdraw factors the O key steps through
+const OVERDRAW_STEPS: [f32; 5] = [1.0, 2.0, 4.0, 8.0, 16.0];
+
+// What each object (and the frame around them) costs beyond a plain opaque draw call
+struct ObjectLoad {
+    // Physics mode for the 2D objects (see physics.rs)
+    physics: bool,
+    blend: Blend,
+    // Objects are drawn squeezed into 1/overdraw of their usual area
+    overdraw: f32,
+    // Black edges over the 3D objects, a second draw per object
+    wireframe: bool,
+    // Lighting of the 3D objects (see lighting.rs)
+    shading: Shading,
+    // Render-to-texture and post-processing passes (see postfx.rs)
+    post: PostPasses,
+}
+
+// Pixels the 2D objects cover per frame, counting overlaps
+fn pixels_per_frame(circles: &[Circle], rectangles: &[Rectangle2D]) -> f64 {
+    let circle_pixels: f64 = circles.iter().map(|c| std::f64::consts::PI * (c.radius as f64).powi(2)).sum();
+    let rectangle_pixels: f64 = rectangles.iter().map(|r| r.width as f64 * r.height as f64).sum();
+    circle_pixels + rectangle_pixels
+}
+
+fn main() -> ExitCode {
+    let options = Options::from_args();
     let (screen_width, screen_height) = (800, 600);
     
+    // In-process profiler when built with the puffin or tracy feature; it
+    // has to run before the first scope
+    let mut profiler = Profiler::start().unwrap_or_else(|e| {
+        eprintln!("Failed to start the profiler: {}", e);
+        std::process::exit(1);
+    });
+    
+    // Optional scripted run and baseline to check it against
+    let mut scenario = options.scenario_path.as_ref().map(|path| {
+        Scenario::load(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load scenario: {}", e);
+            std::process::exit(1);
+        })
+    });
+    let baseline = options.baseline_path.as_ref().map(|path| {
+        report::read_report(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load baseline: {}", e);
+            std::process::exit(1);
+        })
+    });
+    
+    // Optional automatic count search, which drives the run like a scenario
+    let mut ramp = options.ramp.then(|| Ramp::new(
+        options.ramp_target_ms,
+        options.ramp_settle,
+        options.ramp_hold,
+        options.model_path.is_some()
+    ));
+    
+    // Optional per-frame log
+    let mut frame_log = options.log_path.as_ref().map(|path| {
+        FrameLog::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create log file {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    
+    // Frames are paced by our own limiter instead of raylib's set_target_fps
+    // (see pacing.rs). A ramp runs uncapped, so a step's frame times are what
+    // the scene costs rather than the limiter's 16.7 ms.
+    let pacing = options.pacing.unwrap_or(if options.ramp { Pacing::Uncapped } else { Pacing::Fixed(60) });
+    println!("Pacing: {}", pacing.name());
+    
     // Initialize raylib
-    let (mut rl_handle, thread) = raylib::init()
-        .size(screen_width, screen_height)
-        .title("Rust Raylib Object Rendering Test")
-        .build();
+    let mut builder = raylib::init();
+    builder.size(screen_width, screen_height).title("Rust Raylib Object Rendering Test");
+    if pacing == Pacing::Vsync {
+        builder.vsync();
+    }
+    let (mut rl_handle, thread) = builder.build();
+    
+    if options.headless {
+        rl_handle.set_window_state(WindowState::default().set_window_hidden(true));
+    }
     
-    // Set target FPS
-    rl_handle.set_target_fps(60);
+    // Meshes for the 3D shape types (needs the window's GL context)
+    let mut shape_models = ShapeModels::load(
+        &mut rl_handle,
+        &thread,
+        &options.mesh_detail,
+        options.model_path.as_deref()
+    ).unwrap_or_else(|e| {
+        eprintln!("Failed to load 3D shapes: {}", e);
+        std::process::exit(1);
+    });
     
-    // Initialize random number generator
-    let mut rng = rand::thread_rng();
+    // Initialize random number generator; the same seed gives the same scene
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Seed: {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
     
     // Rendering parameters
     let mut render_mode = RenderMode::Mode2D;
@@ -72,6 +279,23 @@ fn main() {
     let mut base_object_count = 100;
     let mut power_multiplier = 1;
     
+    // Physics, blending and overdraw settings
+    let mut load = ObjectLoad {
+        physics: options.physics,
+        blend: options.blend,
+        overdraw: options.overdraw,
+        wireframe: options.wireframe,
+        shading: options.shading,
+        post: options.post,
+    };
+    let mut physics = Physics::new(options.gravity);
+    let mut physics_stats = PhysicsStats::default();
+    
+    // The exact number of objects; base/power changes set it to their product
+    let mut object_count = options.count.unwrap_or(get_actual_object_count(base_object_count, power_multiplier));
+    // Digits typed so far while entering a count (N key)
+    let mut count_input: Option<String> = None;
+    
     // Object collections
     let mut circles: Vec<Circle> = Vec::new();
     let mut rectangles: Vec<Rectangle2D> = Vec::new();
@@ -85,34 +309,103 @@ fn main() {
         45.0                            // fovy
     );
     
+    // Shaders for the lit path, lighting the scene as seen from the camera
+    let mut lighting = Lighting::load(&mut rl_handle, &thread, options.lights, camera.position).unwrap_or_else(|e| {
+        eprintln!("Failed to load lighting: {}", e);
+        std::process::exit(1);
+    });
+    shape_models.set_shader(lighting.shader(load.shading).as_deref());
+    
+    // Render textures and shaders for the post-processing passes
+    let mut postfx = PostFx::load(&mut rl_handle, &thread, screen_width, screen_height).unwrap_or_else(|e| {
+        eprintln!("Failed to set up post-processing: {}", e);
+        std::process::exit(1);
+    });
+    
+    // Frame capture; its time is taken back out of the frame it happened in
+    let mut capture = if options.capture.enabled() {
+        // Frames come at the pacing's rate, or the display's with vsync
+        let fps = match pacing {
+            Pacing::Vsync => u32::try_from(get_monitor_refresh_rate(get_current_monitor())).ok().filter(|&hz| hz > 0),
+            _ => pacing.fps(),
+        };
+        let capture = Capture::start(options.capture, screen_width, screen_height, fps).unwrap_or_else(|e| {
+            eprintln!("Failed to start frame capture: {}", e);
+            std::process::exit(1);
+        });
+        println!("Capturing {}", capture.describe());
+        Some(capture)
+    } else {
+        None
+    };
+    
     // Performance measurement variables
     let mut frame_times = vec![0.0; 120];
     let mut frame_index = 0;
     let mut max_frame_time = 0.0;
     let mut last_max_reset_time = Instant::now();
     let mut last_processing_time = 0.0;
+    // Object adds/removes are timed on their own and kept out of the frame statistics
+    let mut last_resize: Option<(usize, usize, f32)> = None;
+    let mut resized_last_frame = false;
+    let mut resize_ms = 0.0;
+    let mut frame_number: u64 = 0;
+    // Frame and phase times all come from this clock
+    let clock = Clock::new(options.clock);
+    println!("Clock: {}", clock.source.name());
+    let mut clock_check = ClockCheck::default();
+    let sampler = Sampler::start(options.sample_hz);
+    println!("Memory sampling: {}", sampler.describe());
+    // The last frame without and with its capture time; raylib's includes it
+    let mut last_frame_ns = 0;
+    let mut last_raw_ns = 0;
+    let mut frame_start = clock.now();
+    let run_start = Instant::now();
+    let mut recorder = RunRecorder::default();
     
     // Initialize objects
-    initialize_objects(
+    resize_objects(
         &mut circles,
         &mut rectangles,
         &mut cubes,
         &render_mode,
         &shape_type,
-        base_object_count,
-        power_multiplier,
+        object_count,
         &mut rng
     );
     
     // Main game loop
     while !rl_handle.window_should_close() {
-        let delta_time = rl_handle.get_frame_time();
+        profiler.new_frame();
+        
+        let elapsed = run_start.elapsed().as_secs_f64();
+        if scenario.as_ref().is_some_and(|s| s.finished(elapsed)) {
+            break;
+        }
+        
+        let delta_time = last_frame_ns as f32 / 1_000_000_000.0;
+        if frame_number > 0 {
+            clock_check.add(last_raw_ns, rl_handle.get_frame_time());
+        }
+        let mut capture_ns = 0;
+        // The frame that just ended included adding/removing objects
+        let resize_frame = resized_last_frame;
+        resized_last_frame = false;
+        
+        // The ramp judges each step by the frames that ran with its objects
+        let ramp_actions = match &mut ramp {
+            Some(ramp) => ramp.update(elapsed, delta_time * 1000.0),
+            None => Vec::new(),
+        };
+        if ramp.as_ref().is_some_and(|r| r.finished()) {
+            break;
+        }
         
         // Record frame time
         frame_times[frame_index] = delta_time;
         frame_index = (frame_index + 1) % frame_times.len();
         
-        if delta_time > max_frame_time {
+        if delta_time > max_frame_time && !resize_frame {
             max_frame_time = delta_time;
         }
         
@@ -123,194 +416,194 @@ fn main() {
         }
         
         // Start processing time measurement
-        let processing_start = Instant::now();
+        let processing_start = clock.now();
         
-        // Update animations
-        update_animations(&mut circles, &mut rectangles, &mut cubes, delta_time);
+        // Update animations; the physics step is timed on its own
+        let physics_time = if load.physics && matches!(render_mode, RenderMode::Mode2D) {
+            let physics_start = clock.now();
+            physics_stats = physics.step(&mut circles, &mut rectangles, delta_time);
+            ms(clock.ns_since(physics_start))
+        } else {
+            update_animations(&mut circles, &mut rectangles, &mut cubes, delta_time);
+            0.0
+        };
         
-        // Handle input
-        // Toggle between 2D and 3D with TAB key
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_TAB) {
-            render_mode = match render_mode {
-                RenderMode::Mode2D => RenderMode::Mode3D,
-                RenderMode::Mode3D => RenderMode::Mode2D,
-            };
-            println!("Switched to {} rendering mode", 
-                     match render_mode {
-                         RenderMode::Mode2D => "2D",
-                         RenderMode::Mode3D => "3D",
-                     });
-            
-            initialize_objects(
-                &mut circles,
-                &mut rectangles,
-                &mut cubes,
-                &render_mode,
-                &shape_type,
-                base_object_count,
-                power_multiplier,
-                &mut rng
-            );
-        }
-        
-        // Toggle shape type with S key
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_S) {
-            shape_type = match shape_type {
-                ShapeType::Circle => ShapeType::Rectangle,
-                ShapeType::Rectangle => ShapeType::Mixed,
-                ShapeType::Mixed => ShapeType::Circle,
-            };
-            println!("Shape type set to: {}", 
-                     match shape_type {
-                         ShapeType::Circle => "circle",
-                         ShapeType::Rectangle => "rectangle",
-                         ShapeType::Mixed => "mixed",
-                     });
-            
-            initialize_objects(
-                &mut circles,
-                &mut rectangles,
-                &mut cubes,
-                &render_mode,
-                &shape_type,
-                base_object_count,
-                power_multiplier,
-                &mut rng
-            );
-        }
-        
-        // Adjust base object count with UP/DOWN keys
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_UP) {
-            base_object_count = (base_object_count + 10).min(1000);
-            println!("Base value: {} (Total: {})", 
-                     base_object_count, 
-                     get_actual_object_count(base_object_count, power_multiplier));
-            
-            initialize_objects(
-                &mut circles,
-                &mut rectangles,
-                &mut cubes,
-                &render_mode,
-                &shape_type,
-                base_object_count,
-                power_multiplier,
-                &mut rng
-            );
+        // Handle input: ramp or scenario steps that are due, then keys
+        let mut actions = match &mut scenario {
+            Some(scenario) => scenario.due(elapsed),
+            None => Vec::new(),
+        };
+        actions.extend(ramp_actions);
+        // Keys would disturb the ramp's measurements
+        if ramp.is_none() {
+            // N starts typing an exact count (and cancels it again)
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_N) {
+                count_input = match count_input {
+                    Some(_) => None,
+                    None => Some(String::new()),
+                };
+            }
         }
-        
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_DOWN) {
-            base_object_count = (base_object_count - 10).max(10);
-            println!("Base value: {} (Total: {})", 
-                     base_object_count, 
-                     get_actual_object_count(base_object_count, power_multiplier));
-            
-            initialize_objects(
-                &mut circles,
-                &mut rectangles,
-                &mut cubes,
-                &render_mode,
-                &shape_type,
-                base_object_count,
-                power_multiplier,
-                &mut rng
-            );
+        if let Some(input) = &mut count_input {
+            // While typing, keys go to the input
+            while let Some(c) = rl_handle.get_char_pressed() {
+                if c.is_ascii_digit() && input.len() < 7 {
+                    input.push(c);
+                }
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+                input.pop();
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                match input.parse() {
+                    Ok(count) => actions.push(Action::SetCount(count)),
+                    Err(_) => println!("No count entered"),
+                }
+                count_input = None;
+            }
+        } else if ramp.is_none() {
+            // Toggle between 2D and 3D with TAB key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_TAB) {
+                actions.push(Action::ToggleMode);
+            }
+            // Toggle shape type with S key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_S) {
+                actions.push(Action::CycleShape);
+            }
+            // Adjust base object count with UP/DOWN keys
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_UP) {
+                actions.push(Action::AdjustBase(10));
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_DOWN) {
+                actions.push(Action::AdjustBase(-10));
+            }
+            // Adjust power multiplier with LEFT/RIGHT keys
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+                actions.push(Action::AdjustPower(1));
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_LEFT) {
+                actions.push(Action::AdjustPower(-1));
+            }
+            // Toggle the physics mode with P key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_P) {
+                actions.push(Action::TogglePhysics);
+            }
+            // Cycle blending with B key and overdraw with O key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_B) {
+                actions.push(Action::CycleBlend);
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_O) {
+                actions.push(Action::CycleOverdraw);
+            }
+            // Toggle the 3D wireframe with W key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_W) {
+                actions.push(Action::ToggleWireframe);
+            }
+            // Cycle the 3D shading with L key
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_L) {
+                actions.push(Action::CycleShading);
+            }
+            // Render to texture with R key, post-processing passes on F7..F9
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_R) {
+                actions.push(Action::TogglePost);
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_F7) {
+                actions.push(Action::TogglePass(Pass::Blur));
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_F8) {
+                actions.push(Action::TogglePass(Pass::Bloom));
+            }
+            if rl_handle.is_key_pressed(KeyboardKey::KEY_F9) {
+                actions.push(Action::TogglePass(Pass::Grade));
+            }
+            // Scale the count logarithmically with the mouse wheel
+            let wheel = rl_handle.get_mouse_wheel_move();
+            if wheel != 0.0 {
+                actions.push(Action::ScaleCount(wheel));
+            }
+            // Presets on F1..F6
+            let preset_keys = [
+                KeyboardKey::KEY_F1,
+                KeyboardKey::KEY_F2,
+                KeyboardKey::KEY_F3,
+                KeyboardKey::KEY_F4,
+                KeyboardKey::KEY_F5,
+                KeyboardKey::KEY_F6,
+            ];
+            for (key, count) in preset_keys.into_iter().zip(COUNT_PRESETS) {
+                if rl_handle.is_key_pressed(key) {
+                    actions.push(Action::SetCount(count));
+                }
+            }
         }
         
-        // Adjust power multiplier with LEFT/RIGHT keys
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_RIGHT) {
-            power_multiplier = (power_multiplier + 1).min(5);
-            println!("Power multiplier: 10^{} (Total: {})", 
-                     power_multiplier - 1, 
-                     get_actual_object_count(base_object_count, power_multiplier));
+        if !actions.is_empty() {
+            for action in actions {
+                apply_action(
+                    action,
+                    &mut render_mode,
+                    &mut shape_type,
+                    &mut base_object_count,
+                    &mut power_multiplier,
+                    &mut object_count,
+                    &mut load
+                );
+            }
+            // The model shape type needs --model
+            if shape_type == ShapeType::Model && !shape_models.has_model() {
+                println!("No model loaded (see --model), using cubes");
+                shape_type = ShapeType::Cube;
+            }
+            // The models draw with their materials' shader
+            shape_models.set_shader(lighting.shader(load.shading).as_deref());
             
-            initialize_objects(
+            let resize_start = clock.now();
+            let (added, removed) = resize_objects(
                 &mut circles,
                 &mut rectangles,
                 &mut cubes,
                 &render_mode,
                 &shape_type,
-                base_object_count,
-                power_multiplier,
+                object_count,
                 &mut rng
             );
+            resize_ms = ms(clock.ns_since(resize_start));
+            if added > 0 || removed > 0 {
+                println!("Added {} and removed {} objects in {:.2} ms", added, removed, resize_ms);
+                last_resize = Some((added, removed, resize_ms));
+                resized_last_frame = true;
+            }
         }
         
-        if rl_handle.is_key_pressed(KeyboardKey::KEY_LEFT) {
-            power_multiplier = (power_multiplier - 1).max(1);
-            println!("Power multiplier: 10^{} (Total: {})", 
-                     power_multiplier - 1, 
-                     get_actual_object_count(base_object_count, power_multiplier));
-            
-            initialize_objects(
-                &mut circles,
-                &mut rectangles,
-                &mut cubes,
-                &render_mode,
-                &shape_type,
-                base_object_count,
-                power_multiplier,
-                &mut rng
-            );
+        // End processing time measurement (without the resize and physics)
+        last_processing_time = ms(clock.ns_since(processing_start)) - physics_time;
+        if resized_last_frame {
+            last_processing_time -= resize_ms;
         }
         
-        // End processing time measurement
-        last_processing_time = processing_start.elapsed().as_secs_f32() * 1000.0;
-        
         // Drawing
         let mut d = rl_handle.begin_drawing(&thread);
         
-        d.clear_background(Color::WHITE);
-        
-        // Draw based on current render mode
-        match render_mode {
-            RenderMode::Mode2D => {
-                // Draw circles
-                for circle in &circles {
-                    d.draw_circle(
-                        circle.x as i32,
-                        circle.y as i32,
-                        circle.radius,
-                        circle.color,
-                    );
-                }
-                
-                // Draw rectangles
-                for rect in &rectangles {
-                    d.draw_rectangle(
-                        rect.x as i32,
-                        rect.y as i32,
-                        rect.width as i32,
-                        rect.height as i32,
-                        rect.color,
-                    );
-                }
-            },
-            RenderMode::Mode3D => {
-                // 3D rendering
-                let mut camera_3d = d.begin_mode3D(camera);
-                
-                // Draw grid
-                camera_3d.draw_grid(20, 1.0);
-                
-                // Draw cubes
-                for cube in &cubes {
-                    camera_3d.draw_cube_v(
-                        cube.position,
-                        cube.size,
-                        cube.color,
-                    );
-                    camera_3d.draw_cube_wires_v(
-                        cube.position,
-                        cube.size,
-                        Color::BLACK,
-                    );
-                }
-            }
+        let scene = Scene {
+            render_mode,
+            shape_type,
+            load: &load,
+            circles: &circles,
+            rectangles: &rectangles,
+            cubes: &cubes,
+            camera,
+            shape_models: &shape_models,
+        };
+        if load.post.enabled {
+            // Through the render texture and the post-processing passes
+            postfx.draw(&mut d, &thread, &clock, load.post, |target| draw_scene(target, &scene, &mut lighting));
+        } else {
+            draw_scene(&mut d, &scene, &mut lighting);
         }
         
         // Display information
-        let current_memory = get_memory_usage();
-        let actual_count = get_actual_object_count(base_object_count, power_multiplier);
+        let current_memory = sampler.memory_mb();
+        let formula_count = get_actual_object_count(base_object_count, power_multiplier);
         
         // Helper function to draw text with background
         fn draw_text_with_bg(d: &mut RaylibDrawHandle, text: &str, x: i32, y: i32, font_size: i32, color: Color) {
@@ -327,54 +620,488 @@ fn main() {
         
         // Draw performance information
         let fps = d.get_fps();
-        draw_text_with_bg(&mut d, &format!("FPS: {}", fps), 20, 20, 20, Color::BLACK);
+        draw_text_with_bg(&mut d, &format!("FPS: {} (pacing: {}, clock: {})", fps, pacing.name(), clock.source.name()), 20, 20, 20, Color::BLACK);
+        let sampling = sampler.cost();
         draw_text_with_bg(&mut d, &format!("Memory Usage: {:.2} MB", current_memory), 20, 50, 20, Color::BLACK);
+        draw_text_with_bg(&mut d, &format!("Sampling: {}, {:.1} us ({:.2}% CPU)", sampler.describe(), sampling.mean_us, sampling.percent), 400, 50, 18, Color::DARKGRAY);
         draw_text_with_bg(&mut d, &format!("Max Frame Time: {:.2} ms", max_frame_time * 1000.0), 20, 80, 20, Color::BLACK);
         draw_text_with_bg(&mut d, &format!("Last Processing Time: {:.2} ms", last_processing_time), 20, 110, 20, Color::DARKBLUE);
+        if let Some((added, removed, ms)) = last_resize {
+            draw_text_with_bg(&mut d, &format!("Last Resize: +{} / -{} in {:.2} ms", added, removed, ms), 400, 110, 18, Color::DARKBLUE);
+        }
         
-        // Draw rendering information
-        draw_text_with_bg(&mut d, &format!("Mode: {}", 
-                                          match render_mode {
-                                              RenderMode::Mode2D => "2d",
-                                              RenderMode::Mode3D => "3d",
-                                          }), 20, 150, 20, Color::DARKGREEN);
+        if load.physics {
+            draw_text_with_bg(
+                &mut d,
+                &format!("Physics: {:.2} ms, {} pairs, {} collisions",
+                         physics_time,
+                         physics_stats.pairs_tested,
+                         physics_stats.collisions),
+                400, 150, 18, Color::DARKBLUE
+            );
+        }
         
-        draw_text_with_bg(&mut d, &format!("Shape Type: {}", 
-                                          match shape_type {
-                                              ShapeType::Circle => "circle",
-                                              ShapeType::Rectangle => "rectangle",
-                                              ShapeType::Mixed => "mixed",
-                                          }), 20, 180, 20, Color::DARKGREEN);
+        // Blending, overdraw and, in 2D, an estimate of the fill rate they cost
+        draw_text_with_bg(&mut d, &format!("Blend: {}, Overdraw: {}x", load.blend.name(), load.overdraw), 400, 180, 18, Color::DARKBLUE);
+        if let RenderMode::Mode3D = render_mode {
+            let shading = match load.shading {
+                Shading::Unlit => "unlit".to_string(),
+                shading => format!("per-{} ({} lights)", shading.name(), lighting.light_count),
+            };
+            draw_text_with_bg(
+                &mut d,
+                &format!("Wireframe: {}, Shading: {}", if load.wireframe { "on" } else { "off" }, shading),
+                400, 210, 18, Color::DARKBLUE
+            );
+        }
+        if let RenderMode::Mode2D = render_mode {
+            // Objects are spread over 800x400 pixels before squeezing
+            let pixels = pixels_per_frame(&circles, &rectangles);
+            let region = 800.0 * 400.0 / load.overdraw as f64;
+            draw_text_with_bg(
+                &mut d,
+                &format!("Fill: {:.2} Mpx/frame, {:.1} layers, {:.0} Mpx/s",
+                         pixels / 1e6,
+                         pixels / region,
+                         pixels * fps as f64 / 1e6),
+                400, 210, 18, Color::DARKBLUE
+            );
+        }
+        
+        // Time per post-processing pass in this frame
+        if load.post.enabled {
+            let times = postfx.times;
+            let pass_ms = |enabled: bool, ms: f32| if enabled { format!("{:.2}", ms) } else { "-".to_string() };
+            draw_text_with_bg(
+                &mut d,
+                &format!("Render to texture, submit: scene {:.2} ms, present {:.2} ms", times.scene_submit_ms, times.present_submit_ms),
+                400, 240, 18, Color::DARKBLUE
+            );
+            draw_text_with_bg(
+                &mut d,
+                &format!("Submit: blur {}, bloom {}, grade {} ms",
+                         pass_ms(load.post.blur, times.blur_submit_ms),
+                         pass_ms(load.post.bloom, times.bloom_submit_ms),
+                         pass_ms(load.post.grade, times.grade_submit_ms)),
+                400, 270, 18, Color::DARKBLUE
+            );
+        }
+        
+        // Draw rendering information
+        draw_text_with_bg(&mut d, &format!("Mode: {}", render_mode.name()), 20, 150, 20, Color::DARKGREEN);
+        draw_text_with_bg(&mut d, &format!("Shape Type: {}", shape_type.name()), 20, 180, 20, Color::DARKGREEN);
         
         draw_text_with_bg(&mut d, &format!("Base Value: {}", base_object_count), 20, 210, 20, Color::DARKGREEN);
         draw_text_with_bg(&mut d, &format!("Power Multiplier: 10^{}", power_multiplier - 1), 20, 240, 20, Color::DARKGREEN);
-        draw_text_with_bg(&mut d, &format!("Total Objects: {}", actual_count), 20, 270, 20, Color::DARKGREEN);
+        match &count_input {
+            Some(input) => {
+                draw_text_with_bg(&mut d, &format!("Enter count: {}_ (Enter: apply, N: cancel)", input), 20, 270, 20, Color::RED);
+            }
+            None => {
+                draw_text_with_bg(&mut d, &format!("Total Objects: {}", object_count), 20, 270, 20, Color::DARKGREEN);
+            }
+        }
         
         // Display triangle and vertex count in 3D mode
         if let RenderMode::Mode3D = render_mode {
-            let triangle_count = actual_count * 12; // Each cube has 12 triangles
-            let vertex_count = actual_count * 36;   // Each cube has 36 vertices
+            // Per object, from the shape type's mesh
+            let (triangles, vertices) = shape_models.counts(shape_type);
+            let triangle_count = object_count as i64 * triangles;
+            let vertex_count = object_count as i64 * vertices;
             
             draw_text_with_bg(&mut d, &format!("Triangle Count: {}", triangle_count), 20, 300, 20, Color::DARKGREEN);
             draw_text_with_bg(&mut d, &format!("Vertex Count: {}", vertex_count), 20, 330, 20, Color::DARKGREEN);
         }
         
+        if let Some(ramp) = &ramp {
+            draw_text_with_bg(&mut d, &ramp.status(), 400, 20, 18, Color::RED);
+        }
+        
         // Help instructions
-        draw_text_with_bg(&mut d, "Controls:", 20, 380, 20, Color::DARKGRAY);
-        draw_text_with_bg(&mut d, "- UP/DOWN: Adjust base value by 10", 40, 410, 18, Color::DARKGRAY);
-        draw_text_with_bg(&mut d, "- LEFT/RIGHT: Adjust power multiplier (10^n)", 40, 440, 18, Color::DARKGRAY);
-        draw_text_with_bg(&mut d, "- S: Cycle shape types (Circle → Rectangle → Mixed)", 40, 470, 18, Color::DARKGRAY);
-        draw_text_with_bg(&mut d, "- TAB: Toggle between 2D and 3D mode", 40, 500, 18, Color::DARKGRAY);
-        draw_text_with_bg(&mut d, "- ESC: Exit", 40, 530, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "Controls:", 20, 375, 20, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- UP/DOWN: Adjust base value by 10", 40, 400, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- LEFT/RIGHT: Adjust power multiplier (10^n)", 40, 425, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- N: Type a count, Wheel: Scale count, F1-F6: Presets", 40, 450, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- S: Cycle shape types (2D: Circle → Rectangle → Mixed, 3D: Cube → ... → Model)", 40, 475, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- TAB: 2D/3D mode, P: Physics, R: Render to texture, F7-F9: Blur/Bloom/Grade", 40, 500, 18, Color::DARKGRAY);
+        draw_text_with_bg(&mut d, "- B: Blending, O: Overdraw, W: Wireframe, L: Lighting, ESC: Exit", 40, 525, 18, Color::DARKGRAY);
+        
+        let formula_text = if object_count == formula_count {
+            format!("Formula: {} × 10^{} = {} objects", base_object_count, power_multiplier - 1, object_count)
+        } else {
+            format!("Custom count: {} objects (UP/DOWN/LEFT/RIGHT return to the formula)", object_count)
+        };
+        draw_text_with_bg(&mut d, &formula_text, 40, 560, 18, Color::DARKBLUE);
+        
+        // Capture the finished frame, overlay included
+        if let Some(capture) = &mut capture {
+            let previous_stalled = delta_time * 1000.0 > options.stall_ms;
+            let capture_start = clock.now();
+            capture.frame(&mut d, &thread, frame_number, previous_stalled);
+            capture_ns = clock.ns_since(capture_start);
+        }
+        
+        // End the frame, then wait out the rest of it; the capture doesn't count
+        {
+            scope!("end_drawing");
+            drop(d);
+        }
+        pacing.wait(&clock, frame_start, capture_ns);
+        let frame_end = clock.now();
+        last_raw_ns = frame_end.ns_since(frame_start);
+        last_frame_ns = last_raw_ns - capture_ns;
+        frame_start = frame_end;
         
-        draw_text_with_bg(
-            &mut d, 
-            &format!("Formula: {} × 10^{} = {} objects", 
-                    base_object_count, 
-                    power_multiplier - 1, 
-                    actual_count), 
-            40, 560, 18, Color::DARKBLUE
+        // Record this frame, with its own time, under the phase it ran in
+        let frame_ms = ms(last_frame_ns);
+        let mut phase = format!("{}-{}-{}", render_mode.name(), shape_type.name(), object_count);
+        if load.physics {
+            phase.push_str("+physics");
+        }
+        if !matches!(load.blend, Blend::Opaque) {
+            phase.push('+');
+            phase.push_str(load.blend.name());
+        }
+        if load.overdraw > 1.0 {
+            phase.push_str(&format!("+overdraw{}", load.overdraw));
+        }
+        if matches!(render_mode, RenderMode::Mode3D) && !load.wireframe {
+            phase.push_str("+solid");
+        }
+        if matches!(render_mode, RenderMode::Mode3D) && load.shading != Shading::Unlit {
+            phase.push_str(&format!("+{}-lit", load.shading.name()));
+        }
+        if load.post.enabled {
+            phase.push_str("+rtt");
+            for pass in [Pass::Blur, Pass::Bloom, Pass::Grade] {
+                if load.post.get(pass) {
+                    phase.push('+');
+                    phase.push_str(pass.name());
+                }
+            }
+        }
+        if !resized_last_frame {
+            recorder.record(&phase, frame_ms, current_memory);
+        }
+        if let Some(log) = &mut frame_log {
+            let record = FrameRecord {
+                frame: frame_number,
+                time_ms: run_start.elapsed().as_secs_f64() * 1000.0,
+                phase: &phase,
+                frame_ms,
+                processing_ms: last_processing_time,
+                physics_ms: physics_time,
+                resize_ms: if resized_last_frame { resize_ms } else { 0.0 },
+                post: if load.post.enabled { postfx.times } else { PassTimes::default() },
+                memory_mb: current_memory,
+            };
+            if let Err(e) = log.record(&record) {
+                eprintln!("Failed to write frame log: {}", e);
+                frame_log = None;
+            }
+        }
+        frame_number += 1;
+    }
+    
+    // Close the window before reporting; the models go with the GL context
+    drop(shape_models);
+    drop(lighting);
+    drop(postfx);
+    drop(rl_handle);
+    
+    println!("Clock check: {:.3} ms drift from raylib's frame times, at most {:.3} ms in a frame",
+             clock_check.drift_ms(),
+             clock_check.max_frame_ms);
+    let sampling = sampler.cost();
+    println!("Memory sampling: {} samples, {:.1} us each (max {:.1} us), {:.3}% of a core",
+             sampling.samples,
+             sampling.mean_us,
+             sampling.max_us,
+             sampling.percent);
+    
+    // Waits for ffmpeg to finish the video
+    if let Some(capture) = capture {
+        println!("Captured {} frames", capture.saved);
+    }
+    
+    let summaries = recorder.summarize(options.stall_ms);
+    if let Some(path) = &options.report_path {
+        let mut metadata = vec![("binary", env!("CARGO_PKG_NAME").to_string())];
+        if let Some(scenario_path) = &options.scenario_path {
+            metadata.push(("scenario", scenario_path.display().to_string()));
+        }
+        metadata.push(("seed", seed.to_string()));
+        metadata.push(("stall_ms", options.stall_ms.to_string()));
+        metadata.push(("pacing", pacing.name()));
+        metadata.push(("clock", clock.source.name().to_string()));
+        metadata.push(("memory_sampling", sampler.describe()));
+        match report::write_report(path, &metadata, &summaries) {
+            Ok(()) => println!("Report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write report {}: {}", path.display(), e),
+        }
+    }
+    
+    if let Some(ramp) = &ramp {
+        ramp::print_results(ramp.results(), options.ramp_target_ms);
+        if let Some(path) = &options.ramp_report_path {
+            let metadata = [
+                ("binary", env!("CARGO_PKG_NAME").to_string()),
+                ("seed", seed.to_string()),
+                ("pacing", pacing.name()),
+                ("memory_sampling", sampler.describe()),
+                ("target_ms", options.ramp_target_ms.to_string()),
+                ("settle_s", options.ramp_settle.to_string()),
+                ("hold_s", options.ramp_hold.to_string()),
+            ];
+            match ramp::write_results(path, &metadata, ramp.results()) {
+                Ok(()) => println!("Ramp results written to {}", path.display()),
+                Err(e) => eprintln!("Failed to write ramp results {}: {}", path.display(), e),
+            }
+        }
+    }
+    
+    if let Some(baseline) = &baseline {
+        let tolerances = Tolerances {
+            frame_percent: options.frame_tolerance,
+            stalls: options.stall_tolerance,
+            memory_percent: options.memory_tolerance,
+        };
+        let regressions = report::compare_with_baseline(baseline, &summaries, &tolerances);
+        if regressions > 0 {
+            eprintln!("{} regression(s) against the baseline", regressions);
+            return ExitCode::FAILURE;
+        }
+        println!("No regressions against the baseline");
+    }
+    
+    ExitCode::SUCCESS
+}
+
+// Apply a key press or scenario step to the rendering parameters
+fn apply_action(
+    action: Action,
+    render_mode: &mut RenderMode,
+    shape_type: &mut ShapeType,
+    base_object_count: &mut i32,
+    power_multiplier: &mut i32,
+    object_count: &mut i32,
+    load: &mut ObjectLoad
+) {
+    match action {
+        Action::SetMode(mode) => *render_mode = mode,
+        Action::ToggleMode => {
+            *render_mode = match render_mode {
+                RenderMode::Mode2D => RenderMode::Mode3D,
+                RenderMode::Mode3D => RenderMode::Mode2D,
+            };
+        }
+        // A shape type of the other mode switches the mode with it
+        Action::SetShape(shape) => {
+            *shape_type = shape;
+            *render_mode = shape.mode();
+        }
+        Action::CycleShape => *shape_type = shape_type.next(),
+        Action::SetBase(base) => *base_object_count = base,
+        Action::AdjustBase(step) => *base_object_count = (*base_object_count + step).clamp(10, 1000),
+        Action::SetPower(power) => *power_multiplier = power,
+        Action::AdjustPower(step) => *power_multiplier = (*power_multiplier + step).clamp(1, 5),
+        Action::SetCount(count) => *object_count = count.clamp(0, MAX_OBJECTS),
+        Action::ScaleCount(notches) => {
+            // Always move by at least one object
+            let scaled = (*object_count as f32 * 10f32.powf(notches / 10.0)).round() as i32;
+            let scaled = if notches > 0.0 { scaled.max(*object_count + 1) } else { scaled.min(*object_count - 1) };
+            *object_count = scaled.clamp(0, MAX_OBJECTS);
+        }
+        Action::SetPhysics(enabled) => load.physics = enabled,
+        Action::TogglePhysics => load.physics = !load.physics,
+        Action::SetBlend(blend) => load.blend = blend,
+        Action::CycleBlend => {
+            load.blend = match load.blend {
+                Blend::Opaque => Blend::Alpha,
+                Blend::Alpha => Blend::Additive,
+                Blend::Additive => Blend::Opaque,
+            };
+        }
+        Action::SetOverdraw(overdraw) => load.overdraw = overdraw,
+        Action::CycleOverdraw => {
+            load.overdraw = OVERDRAW_STEPS
+                .into_iter()
+                .find(|&step| step > load.overdraw)
+                .unwrap_or(OVERDRAW_STEPS[0]);
+        }
+        Action::SetWireframe(enabled) => load.wireframe = enabled,
+        Action::ToggleWireframe => load.wireframe = !load.wireframe,
+        Action::SetPost(enabled) => load.post.enabled = enabled,
+        Action::TogglePost => load.post.enabled = !load.post.enabled,
+        Action::SetPass(pass, enabled) => load.post.set(pass, enabled),
+        Action::TogglePass(pass) => load.post.set(pass, !load.post.get(pass)),
+        Action::SetShading(shading) => load.shading = shading,
+        Action::CycleShading => {
+            load.shading = match load.shading {
+                Shading::Unlit => Shading::Vertex,
+                Shading::Vertex => Shading::Pixel,
+                Shading::Pixel => Shading::Unlit,
+            };
+        }
+    }
+    
+    // Switching modes starts over with the new mode's first shape type
+    if shape_type.mode() != *render_mode {
+        *shape_type = ShapeType::first(*render_mode);
+    }
+    
+    // Base and power define the count through the formula
+    if let Action::SetBase(_) | Action::AdjustBase(_) | Action::SetPower(_) | Action::AdjustPower(_) = action {
+        *object_count = get_actual_object_count(*base_object_count, *power_multiplier).min(MAX_OBJECTS);
+    }
+    
+    match action {
+        Action::SetMode(_) | Action::ToggleMode => {
+            println!("Switched to {} rendering mode", render_mode.name().to_uppercase());
+        }
+        Action::SetShape(_) | Action::CycleShape => {
+            println!("Shape type set to: {}", shape_type.name());
+        }
+        Action::SetBase(_) | Action::AdjustBase(_) => {
+            println!("Base value: {} (Total: {})", base_object_count, object_count);
+        }
+        Action::SetPower(_) | Action::AdjustPower(_) => {
+            println!("Power multiplier: 10^{} (Total: {})", *power_multiplier - 1, object_count);
+        }
+        Action::SetCount(_) | Action::ScaleCount(_) => {
+            println!("Object count: {}", object_count);
+        }
+        Action::SetPhysics(_) | Action::TogglePhysics => {
+            println!("Physics: {}", if load.physics { "on" } else { "off" });
+        }
+        Action::SetBlend(_) | Action::CycleBlend => {
+            println!("Blend: {}", load.blend.name());
+        }
+        Action::SetOverdraw(_) | Action::CycleOverdraw => {
+            println!("Overdraw: {}x", load.overdraw);
+        }
+        Action::SetWireframe(_) | Action::ToggleWireframe => {
+            println!("Wireframe: {}", if load.wireframe { "on" } else { "off" });
+        }
+        Action::SetPost(_) | Action::TogglePost => {
+            println!("Render to texture: {}", if load.post.enabled { "on" } else { "off" });
+        }
+        Action::SetPass(pass, _) | Action::TogglePass(pass) => {
+            println!("Post-processing {}: {}", pass.name(), if load.post.get(pass) { "on" } else { "off" });
+        }
+        Action::SetShading(_) | Action::CycleShading => {
+            println!("Shading: {}", load.shading.name());
+        }
+    }
+}
+
+// Everything the scene is drawn from
+struct Scene<'a> {
+    render_mode: RenderMode,
+    shape_type: ShapeType,
+    load: &'a ObjectLoad,
+    circles: &'a [Circle],
+    rectangles: &'a [Rectangle2D],
+    cubes: &'a [Cube],
+    camera: Camera3D,
+    shape_models: &'a ShapeModels,
+}
+
+// Draw the objects, to the backbuffer or into the post-processing render texture
+fn draw_scene(d: &mut impl RaylibDraw, scene: &Scene, lighting: &mut Lighting) {
+    scope!("draw_scene");
+    
+    // Additive blending needs a dark background to show anything
+    d.clear_background(match scene.load.blend {
+        Blend::Additive => Color::BLACK,
+        _ => Color::WHITE,
+    });
+    
+    // Overdraw squeezes positions towards the center; sizes stay the same
+    let squeeze = 1.0 / scene.load.overdraw.sqrt();
+    let alpha = scene.load.blend.alpha();
+    
+    // Draw based on current render mode
+    match scene.render_mode {
+        RenderMode::Mode2D => {
+            let mut d = d.begin_blend_mode(scene.load.blend.mode());
+            let (center_x, center_y) = (400.0, 300.0);
+            
+            // Draw circles
+            for circle in scene.circles {
+                d.draw_circle(
+                    (center_x + (circle.x - center_x) * squeeze) as i32,
+                    (center_y + (circle.y - center_y) * squeeze) as i32,
+                    circle.radius,
+                    Color::new(circle.color.r, circle.color.g, circle.color.b, alpha),
+                );
+            }
+            
+            // Draw rectangles
+            for rect in scene.rectangles {
+                d.draw_rectangle(
+                    (center_x + (rect.x - center_x) * squeeze) as i32,
+                    (center_y + (rect.y - center_y) * squeeze) as i32,
+                    rect.width as i32,
+                    rect.height as i32,
+                    Color::new(rect.color.r, rect.color.g, rect.color.b, alpha),
+                );
+            }
+        },
+        RenderMode::Mode3D => {
+            // 3D rendering
+            let mut camera_3d = d.begin_mode3D(scene.camera);
+            
+            // Draw grid
+            camera_3d.draw_grid(20, 1.0);
+            
+            // Draw the objects, through the lit shader if there is one
+            let mut camera_3d = camera_3d.begin_blend_mode(scene.load.blend.mode());
+            let shape_model = scene.shape_models.get(scene.shape_type);
+            match lighting.shader(scene.load.shading) {
+                Some(shader) => {
+                    let mut lit = camera_3d.begin_shader_mode(shader);
+                    draw_3d_objects(&mut lit, scene.cubes, shape_model, squeeze, alpha, scene.load.wireframe);
+                }
+                None => draw_3d_objects(&mut camera_3d, scene.cubes, shape_model, squeeze, alpha, scene.load.wireframe),
+            }
+        }
+    }
+}
+
+// Draw the 3D objects as cubes, or with the shape type's model when there is one
+fn draw_3d_objects<D: RaylibDraw3D>(
+    d: &mut D,
+    cubes: &[Cube],
+    shape_model: Option<&ShapeModel>,
+    squeeze: f32,
+    alpha: u8,
+    wireframe: bool
+) {
+    scope!("draw_3d_objects");
+    for cube in cubes {
+        let position = Vector3::new(
+            cube.position.x * squeeze,
+            cube.position.y * squeeze,
+            cube.position.z * squeeze,
         );
+        let color = Color::new(cube.color.r, cube.color.g, cube.color.b, alpha);
+        match shape_model {
+            Some(shape_model) => {
+                let scale = cube.size * shape_model.unit_scale;
+                let axis = Vector3::new(0.0, 1.0, 0.0);
+                d.draw_model_ex(&shape_model.model, position, axis, 0.0, scale, color);
+                if wireframe {
+                    d.draw_model_wires_ex(&shape_model.model, position, axis, 0.0, scale, Color::BLACK);
+                }
+            }
+            None => {
+                d.draw_cube_v(position, cube.size, color);
+                if wireframe {
+                    d.draw_cube_wires_v(position, cube.size, Color::BLACK);
+                }
+            }
+        }
     }
 }
 
@@ -383,127 +1110,94 @@ fn get_actual_object_count(base: i32, multiplier: i32) -> i32 {
     base * 10i32.pow((multiplier - 1) as u32)
 }
 
-// Initialize objects based on current mode and settings
-fn initialize_objects(
+// Grow or shrink the object collections to `count` objects for the current
+// mode and shape type. Objects that are already there are kept; only the
+// difference is created or dropped. Returns (added, removed).
+fn resize_objects(
     circles: &mut Vec<Circle>,
     rectangles: &mut Vec<Rectangle2D>,
     cubes: &mut Vec<Cube>,
     render_mode: &RenderMode,
     shape_type: &ShapeType,
-    base_count: i32,
-    power_multiplier: i32,
-    rng: &mut ThreadRng
-) {
-    let actual_count = get_actual_object_count(base_count, power_multiplier);
+    count: i32,
+    rng: &mut StdRng
+) -> (usize, usize) {
+    scope!("resize_objects");
+    let count = count as usize;
+    let (circle_count, rectangle_count, cube_count) = match render_mode {
+        RenderMode::Mode2D => match shape_type {
+            ShapeType::Circle => (count, 0, 0),
+            ShapeType::Rectangle => (0, count, 0),
+            ShapeType::Mixed => (count - count / 2, count / 2, 0),
+            // 3D shape types never stay selected in 2D mode
+            _ => (count, 0, 0),
+        },
+        RenderMode::Mode3D => (0, 0, count),
+    };
     
-    // Clear existing objects
-    circles.clear();
-    rectangles.clear();
-    cubes.clear();
+    let before = [circles.len(), rectangles.len(), cubes.len()];
+    circles.resize_with(circle_count, || random_circle(rng));
+    rectangles.resize_with(rectangle_count, || random_rectangle(rng));
+    cubes.resize_with(cube_count, || random_cube(rng));
+    let after = [circle_count, rectangle_count, cube_count];
     
-    match render_mode {
-        RenderMode::Mode2D => {
-            println!("Creating {} 2D objects", actual_count);
-            
-            match shape_type {
-                ShapeType::Circle => {
-                    // Create only circles
-                    for _ in 0..actual_count.min(10000) {
-                        circles.push(Circle {
-                            x: rng.gen_range(0.0..800.0),
-                            y: rng.gen_range(100.0..500.0),
-                            radius: rng.gen_range(5.0..25.0),
-                            speed: rng.gen_range(50.0..250.0),
-                            color: Color::new(
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                255,
-                            ),
-                        });
-                    }
-                },
-                ShapeType::Rectangle => {
-                    // Create only rectangles
-                    for _ in 0..actual_count.min(10000) {
-                        rectangles.push(Rectangle2D {
-                            x: rng.gen_range(0.0..800.0),
-                            y: rng.gen_range(100.0..500.0),
-                            width: rng.gen_range(10.0..50.0),
-                            height: rng.gen_range(10.0..50.0),
-                            speed: rng.gen_range(30.0..180.0),
-                            color: Color::new(
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                255,
-                            ),
-                        });
-                    }
-                },
-                ShapeType::Mixed => {
-                    // Create both circles and rectangles
-                    let half_count = actual_count / 2;
-                    
-                    for _ in 0..half_count.min(5000) {
-                        circles.push(Circle {
-                            x: rng.gen_range(0.0..800.0),
-                            y: rng.gen_range(100.0..500.0),
-                            radius: rng.gen_range(5.0..25.0),
-                            speed: rng.gen_range(50.0..250.0),
-                            color: Color::new(
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                255,
-                            ),
-                        });
-                    }
-                    
-                    for _ in 0..half_count.min(5000) {
-                        rectangles.push(Rectangle2D {
-                            x: rng.gen_range(0.0..800.0),
-                            y: rng.gen_range(100.0..500.0),
-                            width: rng.gen_range(10.0..50.0),
-                            height: rng.gen_range(10.0..50.0),
-                            speed: rng.gen_range(30.0..180.0),
-                            color: Color::new(
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                rng.gen_range(0..255),
-                                255,
-                            ),
-                        });
-                    }
-                }
-            }
-        },
-        RenderMode::Mode3D => {
-            println!("Creating {} 3D objects", actual_count);
-            
-            // Create cubes
-            for _ in 0..actual_count.min(5000) {
-                cubes.push(Cube {
-                    position: Vector3::new(
-                        rng.gen_range(-10.0..10.0),
-                        rng.gen_range(-5.0..5.0),
-                        rng.gen_range(-10.0..10.0),
-                    ),
-                    size: Vector3::new(
-                        rng.gen_range(0.5..2.5),
-                        rng.gen_range(0.5..2.5),
-                        rng.gen_range(0.5..2.5),
-                    ),
-                    rotation: rng.gen_range(0.0..360.0),
-                    color: Color::new(
-                        rng.gen_range(0..255),
-                        rng.gen_range(0..255),
-                        rng.gen_range(0..255),
-                        255,
-                    ),
-                });
-            }
-        }
+    let added = before.iter().zip(after).map(|(&b, a)| a.saturating_sub(b)).sum();
+    let removed = before.iter().zip(after).map(|(&b, a)| b.saturating_sub(a)).sum();
+    (added, removed)
+}
+
+fn random_circle(rng: &mut StdRng) -> Circle {
+    Circle {
+        x: rng.random_range(0.0..800.0),
+        y: rng.random_range(100.0..500.0),
+        radius: rng.random_range(5.0..25.0),
+        speed: rng.random_range(50.0..250.0),
+        vy: rng.random_range(-100.0..100.0),
+        color: Color::new(
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            255,
+        ),
+    }
+}
+
+fn random_rectangle(rng: &mut StdRng) -> Rectangle2D {
+    Rectangle2D {
+        x: rng.random_range(0.0..800.0),
+        y: rng.random_range(100.0..500.0),
+        width: rng.random_range(10.0..50.0),
+        height: rng.random_range(10.0..50.0),
+        speed: rng.random_range(30.0..180.0),
+        vy: rng.random_range(-100.0..100.0),
+        color: Color::new(
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            255,
+        ),
+    }
+}
+
+fn random_cube(rng: &mut StdRng) -> Cube {
+    Cube {
+        position: Vector3::new(
+            rng.random_range(-10.0..10.0),
+            rng.random_range(-5.0..5.0),
+            rng.random_range(-10.0..10.0),
+        ),
+        size: Vector3::new(
+            rng.random_range(0.5..2.5),
+            rng.random_range(0.5..2.5),
+            rng.random_range(0.5..2.5),
+        ),
+        rotation: rng.random_range(0.0..360.0),
+        color: Color::new(
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            rng.random_range(0..255),
+            255,
+        ),
     }
 }
 
@@ -514,11 +1208,16 @@ fn update_animations(
     cubes: &mut Vec<Cube>,
     delta_time: f32
 ) {
+    scope!("update_animations");
+    
     // Update circle positions
     for circle in circles.iter_mut() {
         circle.x += circle.speed * delta_time;
         if circle.x > 800.0 + circle.radius {
             circle.x = -circle.radius;
+        } else if circle.x < -circle.radius {
+            // Moving left after the physics mode bounced it
+            circle.x = 800.0 + circle.radius;
         }
     }
     
@@ -527,6 +1226,8 @@ fn update_animations(
         rect.x += rect.speed * delta_time;
         if rect.x > 800.0 + rect.width {
             rect.x = -rect.width;
+        } else if rect.x < -rect.width {
+            rect.x = 800.0 + rect.width;
         }
     }
     
@@ -0,0 +1,127 @@
+use raylib::ffi;
+use raylib::prelude::*;
+use std::path::Path;
+
+use crate::ShapeType;
+
+// Meshes for the 3D shape types other than the cube. Each is loaded once as
+// a model and drawn once per object (one draw call each), scaled so that it
+// fills the same unit box a cube of the same size would.
+pub struct ShapeModel {
+    pub model: Model,
+    // Scale that brings the model to a 1x1x1 box
+    pub unit_scale: f32,
+    pub triangles: i64,
+    pub vertices: i64,
+}
+
+impl ShapeModel {
+    fn new(model: Model) -> ShapeModel {
+        let triangles = model.meshes().iter().map(|mesh| mesh.triangleCount as i64).sum();
+        let vertices = model.meshes().iter().map(|mesh| mesh.vertexCount as i64).sum();
+        let bounds = model.get_model_bounding_box();
+        let extent = (bounds.max.x - bounds.min.x)
+            .max(bounds.max.y - bounds.min.y)
+            .max(bounds.max.z - bounds.min.z);
+        ShapeModel {
+            model,
+            unit_scale: if extent > 0.0 { 1.0 / extent } else { 1.0 },
+            triangles,
+            vertices,
+        }
+    }
+
+    fn from_mesh(rl: &mut RaylibHandle, thread: &RaylibThread, mesh: Mesh) -> Result<ShapeModel, String> {
+        // The model takes ownership of the mesh and unloads it with itself
+        let model = rl
+            .load_model_from_mesh(thread, unsafe { mesh.make_weak() })
+            .map_err(|e| e.to_string())?;
+        Ok(ShapeModel::new(model))
+    }
+}
+
+pub struct ShapeModels {
+    sphere: ShapeModel,
+    cylinder: ShapeModel,
+    plane: ShapeModel,
+    model: Option<ShapeModel>,
+    // What the models' materials were loaded with (raylib's default shader)
+    default_shader: ffi::Shader,
+}
+
+// Mesh detail, from the command line
+pub struct MeshDetail {
+    pub sphere_rings: i32,
+    pub sphere_slices: i32,
+    pub cylinder_slices: i32,
+}
+
+impl ShapeModels {
+    pub fn load(
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        detail: &MeshDetail,
+        model_path: Option<&Path>,
+    ) -> Result<ShapeModels, String> {
+        let sphere = Mesh::gen_mesh_sphere(thread, 0.5, detail.sphere_rings, detail.sphere_slices);
+        let cylinder = Mesh::gen_mesh_cylinder(thread, 0.5, 1.0, detail.cylinder_slices);
+        let plane = Mesh::gen_mesh_plane(thread, 1.0, 1.0, 1, 1);
+
+        let model = match model_path {
+            Some(path) => {
+                let model = rl
+                    .load_model(thread, &path.to_string_lossy())
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                Some(ShapeModel::new(model))
+            }
+            None => None,
+        };
+
+        let sphere = ShapeModel::from_mesh(rl, thread, sphere)?;
+        let default_shader = sphere.model.materials()[0].shader;
+        Ok(ShapeModels {
+            sphere,
+            cylinder: ShapeModel::from_mesh(rl, thread, cylinder)?,
+            plane: ShapeModel::from_mesh(rl, thread, plane)?,
+            model,
+            default_shader,
+        })
+    }
+
+    // Draw the models with `shader` (see lighting.rs), or with the default
+    // shader again for None. The models don't own the shader: unloading a
+    // model leaves its materials' shaders alone.
+    pub fn set_shader(&mut self, shader: Option<&Shader>) {
+        let shader = shader.map_or(self.default_shader, |shader| *shader.as_ref());
+        let models = [&mut self.sphere, &mut self.cylinder, &mut self.plane].into_iter().chain(self.model.as_mut());
+        for shape_model in models {
+            for material in shape_model.model.materials_mut() {
+                material.shader = shader;
+            }
+        }
+    }
+
+    pub fn has_model(&self) -> bool {
+        self.model.is_some()
+    }
+
+    // The model to draw for a 3D shape type; None means draw_cube_v
+    pub fn get(&self, shape: ShapeType) -> Option<&ShapeModel> {
+        match shape {
+            ShapeType::Sphere => Some(&self.sphere),
+            ShapeType::Cylinder => Some(&self.cylinder),
+            ShapeType::Plane => Some(&self.plane),
+            ShapeType::Model => self.model.as_ref(),
+            _ => None,
+        }
+    }
+
+    // Triangles and vertices drawn per object
+    pub fn counts(&self, shape: ShapeType) -> (i64, i64) {
+        match self.get(shape) {
+            Some(shape_model) => (shape_model.triangles, shape_model.vertices),
+            // DrawCubeV emits 12 triangles as 36 vertices
+            None => (12, 36),
+        }
+    }
+}
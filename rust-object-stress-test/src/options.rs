@@ -0,0 +1,298 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::capture::CaptureConfig;
+use crate::clock::ClockSource;
+use crate::lighting::{Shading, MAX_LIGHTS};
+use crate::models::MeshDetail;
+use crate::pacing::Pacing;
+use crate::sampler;
+use crate::postfx::{Pass, PostPasses};
+use crate::{Blend, MAX_OBJECTS};
+
+// Command line options
+pub struct Options {
+    // Per-frame CSV log (see frame_log.rs for the columns)
+    pub log_path: Option<PathBuf>,
+    // Scripted run (see scenario.rs for the format)
+    pub scenario_path: Option<PathBuf>,
+    pub headless: bool,
+    // Per-phase summary written when the run ends
+    pub report_path: Option<PathBuf>,
+    // Report of an earlier run to check this one against
+    pub baseline_path: Option<PathBuf>,
+    pub stall_ms: f32,
+    pub frame_tolerance: f32,
+    pub stall_tolerance: usize,
+    pub memory_tolerance: f32,
+    // Seed for the scene's random placement; random when not given
+    pub seed: Option<u64>,
+    // Exact object count to start with instead of base * 10^(power-1)
+    pub count: Option<i32>,
+    // Start with the physics mode on, and its gravity in px/s^2
+    pub physics: bool,
+    pub gravity: f32,
+    pub blend: Blend,
+    pub overdraw: f32,
+    pub wireframe: bool,
+    // Lighting of the 3D objects and the number of lights in the lit path
+    pub shading: Shading,
+    pub lights: usize,
+    // Render to texture and post-processing passes to start with
+    pub post: PostPasses,
+    // Tessellation of the generated 3D shapes, and a model file for the model shape
+    pub mesh_detail: MeshDetail,
+    pub model_path: Option<PathBuf>,
+    // Frames to save as PNGs or a video (see capture.rs)
+    pub capture: CaptureConfig,
+    // None: fixed:60, or uncapped when ramping
+    pub pacing: Option<Pacing>,
+    // Where frame and phase times come from (see clock.rs)
+    pub clock: ClockSource,
+    // Memory samples per second off the render thread, 0 = every frame on it
+    pub sample_hz: u32,
+    // Search for the largest count that stays under the target (see ramp.rs)
+    pub ramp: bool,
+    pub ramp_target_ms: f32,
+    pub ramp_settle: f64,
+    pub ramp_hold: f64,
+    pub ramp_report_path: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            log_path: None,
+            scenario_path: None,
+            headless: false,
+            report_path: None,
+            baseline_path: None,
+            stall_ms: 1000.0 / 30.0,
+            frame_tolerance: 10.0,
+            stall_tolerance: 0,
+            memory_tolerance: 10.0,
+            seed: None,
+            count: None,
+            physics: false,
+            gravity: 500.0,
+            blend: Blend::Opaque,
+            overdraw: 1.0,
+            wireframe: true,
+            shading: Shading::Unlit,
+            lights: 2,
+            post: PostPasses::default(),
+            mesh_detail: MeshDetail { sphere_rings: 16, sphere_slices: 16, cylinder_slices: 16 },
+            model_path: None,
+            capture: CaptureConfig {
+                every: None,
+                stalls: false,
+                window: 30,
+                dir: PathBuf::from("capture"),
+                video: None,
+            },
+            pacing: None,
+            clock: ClockSource::Instant,
+            sample_hz: 100,
+            ramp: false,
+            ramp_target_ms: 1000.0 / 60.0,
+            ramp_settle: 0.5,
+            ramp_hold: 3.0,
+            ramp_report_path: None,
+        }
+    }
+}
+
+const USAGE: &str = "\
+Usage: rust-raylib-object-stress-test [OPTIONS]
+
+Options:
+  --log <FILE>               Write a per-frame CSV log to FILE
+  --scenario <FILE>          Drive the run from a scenario script and exit at its end
+  --headless                 Keep the window hidden (needs --scenario or --ramp)
+  --report <FILE>            Write a per-phase summary to FILE when the run ends
+  --baseline <FILE>          Compare the run against an earlier report; exit 1 on regression
+  --stall-ms <MS>            Frame time counted as a stall (default: 33.3)
+  --frame-tolerance <PCT>    Allowed p50/p90/p99 increase over the baseline (default: 10)
+  --stall-tolerance <N>      Allowed extra stalls per phase over the baseline (default: 0)
+  --memory-tolerance <PCT>   Allowed peak memory increase over the baseline (default: 10)
+  --seed <N>                 Seed the scene's random number generator to replay a run
+  --count <N>                Start with exactly N objects
+  --physics                  Start with the 2D physics mode on
+  --gravity <PX/S2>          Gravity in the physics mode (default: 500)
+  --blend <opaque|alpha|additive>
+                             How objects are blended (default: opaque)
+  --overdraw <F>             Draw objects squeezed into 1/F of the area (default: 1)
+  --no-wireframe             Start without the edges over the 3D objects
+  --shading <unlit|vertex|pixel>
+                             Lighting of the 3D objects (default: unlit)
+  --lights <N>               Lights in the lit path, 1 to 4 (default: 2)
+  --post                     Draw the scene into a render texture before presenting it
+  --blur, --bloom, --grade   Start with that post-processing pass on (implies --post)
+  --sphere-rings <N>         Rings of the sphere mesh (default: 16)
+  --sphere-slices <N>        Slices of the sphere mesh (default: 16)
+  --cylinder-slices <N>      Slices of the cylinder mesh (default: 16)
+  --model <FILE>             OBJ/glTF model for the model shape type
+  --capture-every <N>        Save every Nth frame
+  --capture-stalls           Save the frames around each stall
+  --capture-window <N>       Frames saved before and after a stall (default: 30)
+  --capture-dir <DIR>        Directory for the captured PNGs (default: capture)
+  --capture-video <FILE>     Pipe the captured frames to ffmpeg instead (default: every frame)
+  --pacing <uncapped|fixed:FPS|vsync|busy:FPS>
+                             How frames are paced (default: fixed:60, uncapped with --ramp)
+  --clock <instant|tsc>      Clock for frame and phase times (default: instant)
+  --sample-hz <N>            Memory samples per second up to 10000, 0 = inline every frame (default: 100)
+  --ramp                     Find the largest object count per mode and shape, then exit
+  --ramp-target-ms <MS>      p99 frame time a ramp step must stay under (default: 16.7)
+  --ramp-settle <S>          Unmeasured time after each ramp step starts (default: 0.5)
+  --ramp-hold <S>            Measured time per ramp step (default: 3)
+  --ramp-report <FILE>       Write the ramp results to FILE
+  -h, --help                 Print this help";
+
+impl Options {
+    pub fn from_args() -> Options {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--log" => options.log_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--scenario" => options.scenario_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--headless" => options.headless = true,
+                "--report" => options.report_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--baseline" => options.baseline_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--stall-ms" => options.stall_ms = parse_value(&arg, args.next()),
+                "--frame-tolerance" => options.frame_tolerance = parse_value(&arg, args.next()),
+                "--stall-tolerance" => options.stall_tolerance = parse_value(&arg, args.next()),
+                "--memory-tolerance" => options.memory_tolerance = parse_value(&arg, args.next()),
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())),
+                "--count" => {
+                    let count = parse_value(&arg, args.next());
+                    if !(0..=MAX_OBJECTS).contains(&count) {
+                        usage_error(&format!("--count must be between 0 and {}", MAX_OBJECTS));
+                    }
+                    options.count = Some(count);
+                }
+                "--physics" => options.physics = true,
+                "--gravity" => options.gravity = parse_value(&arg, args.next()),
+                "--blend" => {
+                    options.blend = match value_of(&arg, args.next()).as_str() {
+                        "opaque" => Blend::Opaque,
+                        "alpha" => Blend::Alpha,
+                        "additive" => Blend::Additive,
+                        _ => usage_error("--blend must be opaque, alpha or additive"),
+                    }
+                }
+                "--overdraw" => {
+                    options.overdraw = parse_value(&arg, args.next());
+                    if options.overdraw < 1.0 {
+                        usage_error("--overdraw must be at least 1");
+                    }
+                }
+                "--no-wireframe" => options.wireframe = false,
+                "--shading" => {
+                    options.shading = match value_of(&arg, args.next()).as_str() {
+                        "unlit" => Shading::Unlit,
+                        "vertex" => Shading::Vertex,
+                        "pixel" => Shading::Pixel,
+                        _ => usage_error("--shading must be unlit, vertex or pixel"),
+                    }
+                }
+                "--lights" => {
+                    options.lights = parse_value(&arg, args.next());
+                    if !(1..=MAX_LIGHTS).contains(&options.lights) {
+                        usage_error(&format!("--lights must be between 1 and {}", MAX_LIGHTS));
+                    }
+                }
+                "--post" => options.post.enabled = true,
+                "--blur" => options.post.set(Pass::Blur, true),
+                "--bloom" => options.post.set(Pass::Bloom, true),
+                "--grade" => options.post.set(Pass::Grade, true),
+                "--sphere-rings" => options.mesh_detail.sphere_rings = parse_detail(&arg, args.next()),
+                "--sphere-slices" => options.mesh_detail.sphere_slices = parse_detail(&arg, args.next()),
+                "--cylinder-slices" => options.mesh_detail.cylinder_slices = parse_detail(&arg, args.next()),
+                "--model" => options.model_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--capture-every" => {
+                    let every = parse_value(&arg, args.next());
+                    if every == 0 {
+                        usage_error("--capture-every must be at least 1");
+                    }
+                    options.capture.every = Some(every);
+                }
+                "--capture-stalls" => options.capture.stalls = true,
+                "--capture-window" => options.capture.window = parse_value(&arg, args.next()),
+                "--capture-dir" => options.capture.dir = PathBuf::from(value_of(&arg, args.next())),
+                "--capture-video" => options.capture.video = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "--clock" => {
+                    options.clock = match value_of(&arg, args.next()).as_str() {
+                        "instant" => ClockSource::Instant,
+                        "tsc" => ClockSource::Tsc,
+                        _ => usage_error("--clock must be instant or tsc"),
+                    }
+                }
+                "--sample-hz" => {
+                    options.sample_hz = parse_value(&arg, args.next());
+                    if options.sample_hz > sampler::MAX_HZ {
+                        usage_error(&format!("--sample-hz must be at most {}", sampler::MAX_HZ));
+                    }
+                }
+                "--pacing" => {
+                    options.pacing = Some(Pacing::parse(&value_of(&arg, args.next())).unwrap_or_else(|| {
+                        usage_error("--pacing must be uncapped, fixed:<fps>, vsync or busy:<fps>")
+                    }))
+                }
+                "--ramp" => options.ramp = true,
+                "--ramp-target-ms" => options.ramp_target_ms = parse_value(&arg, args.next()),
+                "--ramp-settle" => options.ramp_settle = parse_value(&arg, args.next()),
+                "--ramp-hold" => options.ramp_hold = parse_value(&arg, args.next()),
+                "--ramp-report" => options.ramp_report_path = Some(PathBuf::from(value_of(&arg, args.next()))),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => usage_error(&format!("unknown argument '{}'", arg)),
+            }
+        }
+
+        if options.headless && options.scenario_path.is_none() && !options.ramp {
+            usage_error("--headless needs --scenario or --ramp");
+        }
+        if options.ramp && options.scenario_path.is_some() {
+            usage_error("--ramp and --scenario can't be used together");
+        }
+
+        if options.capture.video.is_some() && !options.capture.enabled() {
+            options.capture.every = Some(1);
+        }
+
+        options
+    }
+}
+
+fn value_of(flag: &str, value: Option<String>) -> String {
+    match value {
+        Some(value) => value,
+        None => usage_error(&format!("{} needs a value", flag)),
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    let value = value_of(flag, value);
+    match value.parse() {
+        Ok(parsed) => parsed,
+        Err(_) => usage_error(&format!("invalid value '{}' for {}", value, flag)),
+    }
+}
+
+// Ring/slice counts for the generated meshes
+fn parse_detail(flag: &str, value: Option<String>) -> i32 {
+    let detail = parse_value(flag, value);
+    if !(3..=1024).contains(&detail) {
+        usage_error(&format!("{} must be between 3 and 1024", flag));
+    }
+    detail
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    std::process::exit(2);
+}
@@ -0,0 +1,318 @@
+use crate::profiler::scope;
+use crate::{Circle, Rectangle2D};
+
+// Game-like movement for the 2D objects: velocity, gravity, wall bounces and
+// circle-circle / rectangle-rectangle collisions (circles and rectangles
+// don't collide with each other). Candidate pairs come from a uniform grid
+// whose cells are as large as the largest object, so only the 3x3 cells
+// around an object have to be checked.
+
+const WORLD_WIDTH: f32 = 800.0;
+const WORLD_HEIGHT: f32 = 600.0;
+
+// At least the largest circle diameter and rectangle side (see random_circle/random_rectangle)
+const CELL_SIZE: f32 = 50.0;
+
+// Share of the normal velocity kept after a bounce
+const RESTITUTION: f32 = 0.8;
+
+#[derive(Default)]
+pub struct PhysicsStats {
+    // Pairs that shared a neighbourhood and were tested for overlap
+    pub pairs_tested: usize,
+    pub collisions: usize,
+}
+
+pub struct Physics {
+    pub gravity: f32,
+    grid: Grid,
+}
+
+impl Physics {
+    pub fn new(gravity: f32) -> Physics {
+        Physics { gravity, grid: Grid::default() }
+    }
+
+    pub fn step(&mut self, circles: &mut [Circle], rectangles: &mut [Rectangle2D], dt: f32) -> PhysicsStats {
+        scope!("physics_step");
+        let mut stats = PhysicsStats::default();
+
+        // Integrate and bounce off the walls
+        for c in circles.iter_mut() {
+            c.vy += self.gravity * dt;
+            c.x += c.speed * dt;
+            c.y += c.vy * dt;
+            bounce(&mut c.x, &mut c.speed, c.radius, WORLD_WIDTH - c.radius);
+            bounce(&mut c.y, &mut c.vy, c.radius, WORLD_HEIGHT - c.radius);
+        }
+        for r in rectangles.iter_mut() {
+            r.vy += self.gravity * dt;
+            r.x += r.speed * dt;
+            r.y += r.vy * dt;
+            bounce(&mut r.x, &mut r.speed, 0.0, WORLD_WIDTH - r.width);
+            bounce(&mut r.y, &mut r.vy, 0.0, WORLD_HEIGHT - r.height);
+        }
+
+        // Circle-circle
+        self.grid.build(circles.iter().map(|c| (c.x, c.y)));
+        self.grid.for_each_pair(|i, j| {
+            stats.pairs_tested += 1;
+            stats.collisions += collide_circles(circles, i, j) as usize;
+        });
+
+        // Rectangle-rectangle
+        self.grid.build(rectangles.iter().map(|r| (r.x + r.width / 2.0, r.y + r.height / 2.0)));
+        self.grid.for_each_pair(|i, j| {
+            stats.pairs_tested += 1;
+            stats.collisions += collide_rectangles(rectangles, i, j) as usize;
+        });
+
+        stats
+    }
+}
+
+fn bounce(position: &mut f32, velocity: &mut f32, min: f32, max: f32) {
+    if *position < min {
+        *position = min;
+        *velocity = velocity.abs() * RESTITUTION;
+    } else if *position > max {
+        *position = max;
+        *velocity = -velocity.abs() * RESTITUTION;
+    }
+}
+
+// Equal masses: push the circles apart along the normal and reflect the
+// approaching part of their relative velocity
+fn collide_circles(circles: &mut [Circle], i: usize, j: usize) -> bool {
+    let (a, b) = pair_mut(circles, i, j);
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let distance_sq = dx * dx + dy * dy;
+    let min_distance = a.radius + b.radius;
+    if distance_sq >= min_distance * min_distance || distance_sq == 0.0 {
+        return false;
+    }
+
+    let distance = distance_sq.sqrt();
+    let (nx, ny) = (dx / distance, dy / distance);
+    let push = (min_distance - distance) / 2.0;
+    a.x -= nx * push;
+    a.y -= ny * push;
+    b.x += nx * push;
+    b.y += ny * push;
+
+    let approach = (b.speed - a.speed) * nx + (b.vy - a.vy) * ny;
+    if approach < 0.0 {
+        let impulse = approach * (1.0 + RESTITUTION) / 2.0;
+        a.speed += impulse * nx;
+        a.vy += impulse * ny;
+        b.speed -= impulse * nx;
+        b.vy -= impulse * ny;
+    }
+    true
+}
+
+// Axis-aligned boxes: separate along the axis of least overlap and exchange
+// the velocity along it
+fn collide_rectangles(rectangles: &mut [Rectangle2D], i: usize, j: usize) -> bool {
+    let (a, b) = pair_mut(rectangles, i, j);
+    let overlap_x = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let overlap_y = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {
+        return false;
+    }
+
+    if overlap_x < overlap_y {
+        let direction = if a.x < b.x { 1.0 } else { -1.0 };
+        a.x -= direction * overlap_x / 2.0;
+        b.x += direction * overlap_x / 2.0;
+        if (b.speed - a.speed) * direction < 0.0 {
+            let (va, vb) = (a.speed, b.speed);
+            a.speed = vb * RESTITUTION;
+            b.speed = va * RESTITUTION;
+        }
+    } else {
+        let direction = if a.y < b.y { 1.0 } else { -1.0 };
+        a.y -= direction * overlap_y / 2.0;
+        b.y += direction * overlap_y / 2.0;
+        if (b.vy - a.vy) * direction < 0.0 {
+            let (va, vb) = (a.vy, b.vy);
+            a.vy = vb * RESTITUTION;
+            b.vy = va * RESTITUTION;
+        }
+    }
+    true
+}
+
+fn pair_mut<T>(items: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
+    debug_assert!(i < j);
+    let (left, right) = items.split_at_mut(j);
+    (&mut left[i], &mut right[0])
+}
+
+// Objects bucketed by cell (counting sort), rebuilt every step
+#[derive(Default)]
+struct Grid {
+    columns: usize,
+    rows: usize,
+    // Object indices grouped by cell; cell k's objects are entries[starts[k]..starts[k + 1]]
+    starts: Vec<usize>,
+    entries: Vec<usize>,
+    cells: Vec<usize>,
+}
+
+impl Grid {
+    fn build(&mut self, centers: impl Iterator<Item = (f32, f32)>) {
+        self.columns = (WORLD_WIDTH / CELL_SIZE).ceil() as usize;
+        self.rows = (WORLD_HEIGHT / CELL_SIZE).ceil() as usize;
+        let (columns, rows) = (self.columns, self.rows);
+
+        self.cells.clear();
+        self.cells.extend(centers.map(|(x, y)| {
+            let column = ((x / CELL_SIZE) as usize).min(columns - 1);
+            let row = ((y / CELL_SIZE) as usize).min(rows - 1);
+            row * columns + column
+        }));
+
+        self.starts.clear();
+        self.starts.resize(columns * rows + 1, 0);
+        for &cell in &self.cells {
+            self.starts[cell + 1] += 1;
+        }
+        for k in 1..self.starts.len() {
+            self.starts[k] += self.starts[k - 1];
+        }
+
+        self.entries.clear();
+        self.entries.resize(self.cells.len(), 0);
+        let mut next = self.starts.clone();
+        for (index, &cell) in self.cells.iter().enumerate() {
+            self.entries[next[cell]] = index;
+            next[cell] += 1;
+        }
+    }
+
+    fn cell(&self, column: usize, row: usize) -> &[usize] {
+        let k = row * self.columns + column;
+        &self.entries[self.starts[k]..self.starts[k + 1]]
+    }
+
+    // Every pair of objects in the same or adjacent cells, once, as (lower, higher) index
+    fn for_each_pair(&self, mut f: impl FnMut(usize, usize)) {
+        // Half of the neighbourhood, so each pair of cells is visited once
+        const FORWARD: [(isize, isize); 4] = [(1, 0), (-1, 1), (0, 1), (1, 1)];
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let here = self.cell(column, row);
+                for (n, &a) in here.iter().enumerate() {
+                    for &b in &here[n + 1..] {
+                        f(a.min(b), a.max(b));
+                    }
+                }
+
+                for (dc, dr) in FORWARD {
+                    let (c, r) = (column as isize + dc, row as isize + dr);
+                    if c < 0 || c >= self.columns as isize || r >= self.rows as isize {
+                        continue;
+                    }
+                    for &a in here {
+                        for &b in self.cell(c as usize, r as usize) {
+                            f(a.min(b), a.max(b));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raylib::prelude::Color;
+    use std::collections::HashSet;
+
+    // Centers spread over the world and a little past its edges
+    fn centers(count: usize) -> Vec<(f32, f32)> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 40) as f32 / (1u64 << 24) as f32
+        };
+        (0..count)
+            .map(|_| (next() * (WORLD_WIDTH + 40.0) - 20.0, next() * (WORLD_HEIGHT + 40.0) - 20.0))
+            .collect()
+    }
+
+    fn grid_pairs(centers: &[(f32, f32)]) -> Vec<(usize, usize)> {
+        let mut grid = Grid::default();
+        grid.build(centers.iter().copied());
+        let mut pairs = Vec::new();
+        grid.for_each_pair(|i, j| pairs.push((i, j)));
+        pairs
+    }
+
+    fn cell_of((x, y): (f32, f32)) -> (i64, i64) {
+        let columns = (WORLD_WIDTH / CELL_SIZE).ceil() as i64;
+        let rows = (WORLD_HEIGHT / CELL_SIZE).ceil() as i64;
+        (((x / CELL_SIZE) as i64).clamp(0, columns - 1), ((y / CELL_SIZE) as i64).clamp(0, rows - 1))
+    }
+
+    #[test]
+    fn pairs_are_exactly_the_neighbourhoods_each_once() {
+        let centers = centers(2000);
+        let mut pairs = grid_pairs(&centers);
+        let listed = pairs.len();
+        pairs.sort_unstable();
+        pairs.dedup();
+        assert_eq!(pairs.len(), listed, "a pair was listed twice");
+        assert!(pairs.iter().all(|&(i, j)| i < j));
+
+        let mut expected = Vec::new();
+        for i in 0..centers.len() {
+            for j in i + 1..centers.len() {
+                let ((ci, ri), (cj, rj)) = (cell_of(centers[i]), cell_of(centers[j]));
+                if (ci - cj).abs() <= 1 && (ri - rj).abs() <= 1 {
+                    expected.push((i, j));
+                }
+            }
+        }
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn objects_closer_than_a_cell_are_always_paired() {
+        let centers = centers(2000);
+        let pairs: HashSet<(usize, usize)> = grid_pairs(&centers).into_iter().collect();
+        for i in 0..centers.len() {
+            for j in i + 1..centers.len() {
+                let (dx, dy) = (centers[i].0 - centers[j].0, centers[i].1 - centers[j].1);
+                if dx.abs() < CELL_SIZE && dy.abs() < CELL_SIZE {
+                    assert!(pairs.contains(&(i, j)), "{:?} and {:?} were not paired", centers[i], centers[j]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_circles_are_pushed_apart_and_stop_approaching() {
+        let circle = |x: f32, speed: f32| Circle { x, y: 100.0, radius: 10.0, speed, vy: 0.0, color: Color::RED };
+        let mut circles = [circle(100.0, 50.0), circle(115.0, -50.0)];
+        assert!(collide_circles(&mut circles, 0, 1));
+        assert!(circles[1].x - circles[0].x >= 20.0 - 1e-3);
+        assert!(circles[1].speed >= circles[0].speed);
+        assert!(!collide_circles(&mut circles, 0, 1));
+    }
+
+    #[test]
+    fn walls_reflect_and_damp() {
+        let (mut position, mut velocity) = (-5.0, -100.0);
+        bounce(&mut position, &mut velocity, 0.0, 100.0);
+        assert_eq!((position, velocity), (0.0, 100.0 * RESTITUTION));
+        let (mut position, mut velocity) = (105.0, 100.0);
+        bounce(&mut position, &mut velocity, 0.0, 100.0);
+        assert_eq!((position, velocity), (100.0, -100.0 * RESTITUTION));
+    }
+}
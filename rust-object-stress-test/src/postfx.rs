@@ -0,0 +1,279 @@
+use raylib::prelude::*;
+
+use crate::clock::{ms, Clock};
+
+// Optional post-processing: the scene is drawn into a render texture, run
+// through the enabled passes and only then drawn to the backbuffer, the way
+// most production frames are built. Every pass is timed on the CPU until its
+// batch has been flushed (ending a texture or shader mode draws the batch),
+// so the times cover submitting the work, not the GPU executing it.
+
+// Which passes run, and whether the scene goes through a render texture at all
+#[derive(Clone, Copy, Default)]
+pub struct PostPasses {
+    pub enabled: bool,
+    pub blur: bool,
+    pub bloom: bool,
+    pub grade: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum Pass {
+    Blur,
+    Bloom,
+    Grade,
+}
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Blur => "blur",
+            Pass::Bloom => "bloom",
+            Pass::Grade => "grade",
+        }
+    }
+}
+
+impl PostPasses {
+    pub fn get(&self, pass: Pass) -> bool {
+        match pass {
+            Pass::Blur => self.blur,
+            Pass::Bloom => self.bloom,
+            Pass::Grade => self.grade,
+        }
+    }
+
+    // Turning a pass on turns the pipeline on with it
+    pub fn set(&mut self, pass: Pass, enabled: bool) {
+        match pass {
+            Pass::Blur => self.blur = enabled,
+            Pass::Bloom => self.bloom = enabled,
+            Pass::Grade => self.grade = enabled,
+        }
+        self.enabled |= enabled;
+    }
+}
+
+// Submit time per pass in the last frame, in ms; zero for passes that didn't run
+#[derive(Clone, Copy, Default)]
+pub struct PassTimes {
+    pub scene_submit_ms: f32,
+    pub blur_submit_ms: f32,
+    pub bloom_submit_ms: f32,
+    pub grade_submit_ms: f32,
+    pub present_submit_ms: f32,
+}
+
+// 9-tap separable Gaussian; `direction` is one texel along x or y
+const BLUR_FS: &str = "#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+uniform vec2 direction;
+out vec4 finalColor;
+const float weights[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+void main()
+{
+    vec3 sum = texture(texture0, fragTexCoord).rgb * weights[0];
+    for (int i = 1; i < 5; i++) {
+        sum += texture(texture0, fragTexCoord + direction * float(i)).rgb * weights[i];
+        sum += texture(texture0, fragTexCoord - direction * float(i)).rgb * weights[i];
+    }
+    finalColor = vec4(sum, 1.0);
+}
+";
+
+// Keeps what is brighter than the threshold, for the bloom to blur
+const BRIGHT_FS: &str = "#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+out vec4 finalColor;
+void main()
+{
+    vec3 color = texture(texture0, fragTexCoord).rgb;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    finalColor = vec4(color * smoothstep(0.6, 0.8, luma), 1.0);
+}
+";
+
+// Contrast, saturation and a warm tint
+const GRADE_FS: &str = "#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+out vec4 finalColor;
+void main()
+{
+    vec3 color = (texture(texture0, fragTexCoord).rgb - 0.5) * 1.15 + 0.5;
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luma), color, 1.3) * vec3(1.05, 1.0, 0.92);
+    finalColor = vec4(clamp(color, 0.0, 1.0), 1.0);
+}
+";
+
+// Plain copy; the alpha left in the render texture by blending is dropped
+const COPY_FS: &str = "#version 330
+in vec2 fragTexCoord;
+uniform sampler2D texture0;
+out vec4 finalColor;
+void main()
+{
+    finalColor = vec4(texture(texture0, fragTexCoord).rgb, 1.0);
+}
+";
+
+pub struct PostFx {
+    pub times: PassTimes,
+    // targets[0] receives the scene, the others are ping-pong buffers
+    targets: Vec<RenderTexture2D>,
+    width: i32,
+    height: i32,
+    blur_shader: Shader,
+    blur_direction: i32,
+    bright_shader: Shader,
+    grade_shader: Shader,
+    copy_shader: Shader,
+}
+
+impl PostFx {
+    pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread, width: i32, height: i32) -> Result<PostFx, String> {
+        let mut targets = Vec::new();
+        for _ in 0..4 {
+            let target = rl
+                .load_render_texture(thread, width as u32, height as u32)
+                .map_err(|e| e.to_string())?;
+            targets.push(target);
+        }
+
+        let blur_shader = load_shader(rl, thread, "blur", BLUR_FS)?;
+        let bright_shader = load_shader(rl, thread, "bright", BRIGHT_FS)?;
+        let grade_shader = load_shader(rl, thread, "grade", GRADE_FS)?;
+        let copy_shader = load_shader(rl, thread, "copy", COPY_FS)?;
+        let blur_direction = blur_shader.get_shader_location("direction");
+
+        Ok(PostFx {
+            times: PassTimes::default(),
+            targets,
+            width,
+            height,
+            blur_shader,
+            blur_direction,
+            bright_shader,
+            grade_shader,
+            copy_shader,
+        })
+    }
+
+    // Draw the scene into the render texture, run the enabled passes and
+    // present the result
+    pub fn draw(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        thread: &RaylibThread,
+        clock: &Clock,
+        passes: PostPasses,
+        draw_scene: impl FnOnce(&mut RaylibTextureMode<RaylibDrawHandle>),
+    ) {
+        self.times = PassTimes::default();
+
+        let start = clock.now();
+        {
+            let mut target = d.begin_texture_mode(thread, &mut self.targets[0]);
+            draw_scene(&mut target);
+        }
+        self.times.scene_submit_ms = ms(clock.ns_since(start));
+
+        // Index of the target holding the image so far
+        let mut current = 0;
+
+        if passes.blur {
+            let start = clock.now();
+            current = self.blur(d, thread, current, free(&[current]));
+            self.times.blur_submit_ms = ms(clock.ns_since(start));
+        }
+
+        if passes.bloom {
+            let start = clock.now();
+            let glow = free(&[current]);
+            let output = free(&[current, glow]);
+            pass(d, thread, &mut self.targets, glow, current, &mut self.bright_shader, BlendMode::BLEND_ALPHA);
+            let glow = self.blur(d, thread, glow, output);
+            let output = free(&[current, glow]);
+            pass(d, thread, &mut self.targets, output, current, &mut self.copy_shader, BlendMode::BLEND_ALPHA);
+            pass(d, thread, &mut self.targets, output, glow, &mut self.copy_shader, BlendMode::BLEND_ADDITIVE);
+            current = output;
+            self.times.bloom_submit_ms = ms(clock.ns_since(start));
+        }
+
+        if passes.grade {
+            let start = clock.now();
+            let output = free(&[current]);
+            pass(d, thread, &mut self.targets, output, current, &mut self.grade_shader, BlendMode::BLEND_ALPHA);
+            current = output;
+            self.times.grade_submit_ms = ms(clock.ns_since(start));
+        }
+
+        let start = clock.now();
+        {
+            let mut d = d.begin_shader_mode(&mut self.copy_shader);
+            d.draw_texture_rec(
+                &self.targets[current],
+                flipped(self.width, self.height),
+                Vector2::new(0.0, 0.0),
+                Color::WHITE,
+            );
+        }
+        self.times.present_submit_ms = ms(clock.ns_since(start));
+    }
+
+    // Horizontal pass into `scratch`, vertical pass back into `image`
+    fn blur(&mut self, d: &mut RaylibDrawHandle, thread: &RaylibThread, image: usize, scratch: usize) -> usize {
+        let texel = Vector2::new(1.0 / self.width as f32, 1.0 / self.height as f32);
+        // The scene itself is left alone
+        let output = if image == 0 { free(&[image, scratch]) } else { image };
+
+        self.blur_shader.set_shader_value(self.blur_direction, Vector2::new(texel.x, 0.0));
+        pass(d, thread, &mut self.targets, scratch, image, &mut self.blur_shader, BlendMode::BLEND_ALPHA);
+        self.blur_shader.set_shader_value(self.blur_direction, Vector2::new(0.0, texel.y));
+        pass(d, thread, &mut self.targets, output, scratch, &mut self.blur_shader, BlendMode::BLEND_ALPHA);
+        output
+    }
+}
+
+// A fragment shader on top of raylib's default vertex shader
+fn load_shader(rl: &mut RaylibHandle, thread: &RaylibThread, name: &str, code: &str) -> Result<Shader, String> {
+    let shader = rl.load_shader_from_memory(thread, None, Some(code));
+    if !shader.is_shader_valid() {
+        return Err(format!("the {} shader failed to load", name));
+    }
+    Ok(shader)
+}
+
+// Draw target `read` into target `write` through `shader`
+fn pass(
+    d: &mut RaylibDrawHandle,
+    thread: &RaylibThread,
+    targets: &mut [RenderTexture2D],
+    write: usize,
+    read: usize,
+    shader: &mut Shader,
+    blend: BlendMode,
+) {
+    let [target, source] = targets
+        .get_disjoint_mut([write, read])
+        .expect("a pass reads and writes different render textures");
+    let source_rec = flipped(source.texture.width, source.texture.height);
+
+    let mut d = d.begin_texture_mode(thread, target);
+    let mut d = d.begin_blend_mode(blend);
+    let mut d = d.begin_shader_mode(shader);
+    d.draw_texture_rec(&*source, source_rec, Vector2::new(0.0, 0.0), Color::WHITE);
+}
+
+// The first of the ping-pong targets that isn't in use
+fn free(in_use: &[usize]) -> usize {
+    (1..4).find(|index| !in_use.contains(index)).expect("not enough render textures")
+}
+
+// Render textures are stored upside down
+fn flipped(width: i32, height: i32) -> Rectangle {
+    Rectangle::new(0.0, 0.0, width as f32, -(height as f32))
+}
@@ -0,0 +1,119 @@
+// In-process profiler, selected with a cargo feature:
+//   cargo run --features puffin    # then connect puffin_viewer to 127.0.0.1:8585
+//   cargo run --features tracy     # then connect the Tracy profiler
+// Without either feature the scopes compile to nothing.
+//
+// Allocations are tracked through the global allocator: Tracy records every
+// allocation and free for its memory view; puffin has no allocation events,
+// so it gets the count and bytes of each frame.
+
+#[cfg(all(feature = "puffin", feature = "tracy"))]
+compile_error!("the puffin and tracy features are mutually exclusive");
+
+// Profile the rest of the enclosing block under `name`
+macro_rules! scope {
+    ($name:literal) => {
+        #[cfg(feature = "puffin")]
+        puffin::profile_scope!($name);
+        #[cfg(feature = "tracy")]
+        let _span = tracy_client::span!($name);
+    };
+}
+pub(crate) use scope;
+
+pub struct Profiler {
+    #[cfg(feature = "puffin")]
+    _server: puffin_http::Server,
+    #[cfg(feature = "tracy")]
+    client: tracy_client::Client,
+}
+
+impl Profiler {
+    #[cfg(feature = "puffin")]
+    pub fn start() -> Result<Profiler, String> {
+        let address = format!("127.0.0.1:{}", puffin_http::DEFAULT_PORT);
+        let server = puffin_http::Server::new(&address).map_err(|e| e.to_string())?;
+        puffin::set_scopes_on(true);
+        println!("Profiler: puffin, serving on {}", address);
+        Ok(Profiler { _server: server })
+    }
+
+    #[cfg(feature = "tracy")]
+    pub fn start() -> Result<Profiler, String> {
+        println!("Profiler: tracy");
+        Ok(Profiler { client: tracy_client::Client::start() })
+    }
+
+    #[cfg(not(any(feature = "puffin", feature = "tracy")))]
+    pub fn start() -> Result<Profiler, String> {
+        Ok(Profiler {})
+    }
+
+    // Called once per frame, between frames
+    pub fn new_frame(&mut self) {
+        #[cfg(feature = "puffin")]
+        {
+            {
+                let (allocations, bytes) = counting::take();
+                puffin::profile_scope!("allocations", format!("{} allocations, {} bytes", allocations, bytes));
+            }
+            puffin::GlobalProfiler::lock().new_frame();
+        }
+
+        #[cfg(feature = "tracy")]
+        self.client.frame_mark();
+    }
+}
+
+// The system allocator, wrapped for the profiler
+#[cfg(feature = "puffin")]
+#[global_allocator]
+static GLOBAL: counting::Counting<std::alloc::System> = counting::Counting(std::alloc::System);
+
+#[cfg(feature = "tracy")]
+#[global_allocator]
+static GLOBAL: tracy_client::ProfiledAllocator<std::alloc::System> =
+    tracy_client::ProfiledAllocator::new(std::alloc::System, 0);
+
+#[cfg(feature = "puffin")]
+mod counting {
+    use std::alloc::{GlobalAlloc, Layout};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    static BYTES: AtomicU64 = AtomicU64::new(0);
+
+    // Counts the allocations made through the allocator it wraps
+    pub struct Counting<A>(pub A);
+
+    fn count(size: usize) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    // Allocations and bytes since the last call
+    pub fn take() -> (u64, u64) {
+        (ALLOCATIONS.swap(0, Ordering::Relaxed), BYTES.swap(0, Ordering::Relaxed))
+    }
+
+    unsafe impl<A: GlobalAlloc> GlobalAlloc for Counting<A> {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            self.0.alloc(layout)
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count(layout.size());
+            self.0.alloc_zeroed(layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count(new_size);
+            self.0.realloc(ptr, layout, new_size)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.dealloc(ptr, layout)
+        }
+    }
+}
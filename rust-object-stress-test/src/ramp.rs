@@ -0,0 +1,339 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::report::percentile;
+use crate::scenario::Action;
+use crate::{RenderMode, ShapeType, MAX_OBJECTS};
+
+// Automatic search for the largest object count that still renders in budget.
+//
+// For every render mode and shape type the count is grown along a 1-2-5
+// ladder (10, 20, 50, 100, ...) until a step's p99 frame time goes over the
+// target, then binary-searched between the last passing and the first failing
+// count. Each step is held for `settle` seconds (the re-initialization stall
+// and warm-up are not measured) plus `hold` seconds of measured frames.
+// The model shape type is only searched when a model was loaded.
+
+// Stop bisecting once the bracket is within this fraction of the passing count
+const PRECISION: f32 = 0.05;
+
+const HEADER: &str = "mode,shape,max_count,p99_ms,capped";
+
+pub struct RampResult {
+    pub mode: RenderMode,
+    pub shape: ShapeType,
+    // Largest count whose p99 stayed under the target, None if even the first step failed
+    pub max_count: Option<i32>,
+    pub p99_ms: f32,
+    // The search reached MAX_OBJECTS without failing
+    pub capped: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Search {
+    // Growing along the ladder; the last passing step so far
+    Grow { passed: Option<(i32, f32)> },
+    // Between a passing and a failing count
+    Bisect { passed: (i32, f32), failed: i32 },
+}
+
+struct Step {
+    count: i32,
+    start: f64,
+    samples: Vec<f32>,
+}
+
+pub struct Ramp {
+    target_ms: f32,
+    settle: f64,
+    hold: f64,
+    combos: Vec<(RenderMode, ShapeType)>,
+    combo: usize,
+    search: Search,
+    step: Option<Step>,
+    results: Vec<RampResult>,
+}
+
+impl Ramp {
+    pub fn new(target_ms: f32, settle: f64, hold: f64, has_model: bool) -> Ramp {
+        let mut combos = Vec::new();
+        for shape in [ShapeType::Circle, ShapeType::Rectangle, ShapeType::Mixed] {
+            combos.push((RenderMode::Mode2D, shape));
+        }
+        for shape in [ShapeType::Cube, ShapeType::Sphere, ShapeType::Cylinder, ShapeType::Plane] {
+            combos.push((RenderMode::Mode3D, shape));
+        }
+        if has_model {
+            combos.push((RenderMode::Mode3D, ShapeType::Model));
+        }
+        Ramp {
+            target_ms,
+            settle,
+            hold,
+            combos,
+            combo: 0,
+            search: Search::Grow { passed: None },
+            step: None,
+            results: Vec::new(),
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.combo == self.combos.len()
+    }
+
+    pub fn results(&self) -> &[RampResult] {
+        &self.results
+    }
+
+    // One line for the overlay
+    pub fn status(&self) -> String {
+        let Some(&(mode, shape)) = self.combos.get(self.combo) else {
+            return "Ramp: done".to_string();
+        };
+        let best = match self.search {
+            Search::Grow { passed: Some((count, _)) } | Search::Bisect { passed: (count, _), .. } => count.to_string(),
+            Search::Grow { passed: None } => "-".to_string(),
+        };
+        format!(
+            "Ramp {}/{}: {} {}, trying {} (best {})",
+            self.combo + 1,
+            self.combos.len(),
+            mode.name(),
+            shape.name(),
+            self.step.as_ref().map_or(0, |s| s.count),
+            best
+        )
+    }
+
+    // Feed the last frame time; returns the actions that set up the next
+    // step when one is due
+    pub fn update(&mut self, elapsed: f64, frame_ms: f32) -> Vec<Action> {
+        if self.finished() {
+            return Vec::new();
+        }
+
+        let Some(step) = &mut self.step else {
+            // Very first step
+            let (mode, shape) = self.combos[0];
+            let mut actions = vec![Action::SetMode(mode), Action::SetShape(shape)];
+            actions.extend(self.begin_step(elapsed, 10));
+            return actions;
+        };
+
+        let age = elapsed - step.start;
+        if age < self.settle {
+            return Vec::new();
+        }
+        step.samples.push(frame_ms);
+        if age < self.settle + self.hold {
+            return Vec::new();
+        }
+
+        step.samples.sort_by(f32::total_cmp);
+        let p99 = percentile(&step.samples, 99.0);
+        let count = step.count;
+        let passed = p99 <= self.target_ms;
+
+        // Where to go next, or None when this mode/shape is done
+        let next = match (self.search, passed) {
+            (Search::Grow { .. }, true) if count == MAX_OBJECTS => {
+                self.finish_combo(Some((count, p99)), true);
+                None
+            }
+            (Search::Grow { .. }, true) => {
+                self.search = Search::Grow { passed: Some((count, p99)) };
+                Some(ladder_after(count).min(MAX_OBJECTS))
+            }
+            (Search::Grow { passed: None }, false) => {
+                self.finish_combo(None, false);
+                None
+            }
+            (Search::Grow { passed: Some(best) }, false) => {
+                self.search = Search::Bisect { passed: best, failed: count };
+                self.bisect()
+            }
+            (Search::Bisect { failed, .. }, true) => {
+                self.search = Search::Bisect { passed: (count, p99), failed };
+                self.bisect()
+            }
+            (Search::Bisect { passed, .. }, false) => {
+                self.search = Search::Bisect { passed, failed: count };
+                self.bisect()
+            }
+        };
+
+        match next {
+            Some(count) => self.begin_step(elapsed, count),
+            None if self.finished() => Vec::new(),
+            None => {
+                let (mode, shape) = self.combos[self.combo];
+                let mut actions = vec![Action::SetMode(mode), Action::SetShape(shape)];
+                actions.extend(self.begin_step(elapsed, 10));
+                actions
+            }
+        }
+    }
+
+    // Midpoint of the current bracket, or None (and the result recorded) once it is narrow enough
+    fn bisect(&mut self) -> Option<i32> {
+        let Search::Bisect { passed, failed } = self.search else {
+            return None;
+        };
+        let middle = passed.0 + (failed - passed.0) / 2;
+        let narrow = (failed - passed.0) as f32 <= passed.0 as f32 * PRECISION;
+        if narrow || middle <= passed.0 || middle >= failed {
+            self.finish_combo(Some(passed), false);
+            None
+        } else {
+            Some(middle)
+        }
+    }
+
+    fn finish_combo(&mut self, best: Option<(i32, f32)>, capped: bool) {
+        let (mode, shape) = self.combos[self.combo];
+        let result = RampResult {
+            mode,
+            shape,
+            max_count: best.map(|(count, _)| count),
+            p99_ms: best.map_or(0.0, |(_, p99)| p99),
+            capped,
+        };
+        println!(
+            "Ramp: {} {} -> {}{}",
+            mode.name(),
+            shape.name(),
+            result.max_count.map_or("below 10".to_string(), |count| count.to_string()),
+            if capped { " (MAX_OBJECTS reached)" } else { "" }
+        );
+        self.results.push(result);
+        self.combo += 1;
+        self.search = Search::Grow { passed: None };
+    }
+
+    fn begin_step(&mut self, elapsed: f64, count: i32) -> Vec<Action> {
+        self.step = Some(Step { count, start: elapsed, samples: Vec::new() });
+        vec![Action::SetCount(count)]
+    }
+}
+
+// 10, 20, 50, 100, 200, 500, ...
+fn ladder_after(count: i32) -> i32 {
+    let magnitude = 10i32.pow(count.ilog10());
+    match count / magnitude {
+        1 => 2 * magnitude,
+        2..=4 => 5 * magnitude,
+        _ => 10 * magnitude,
+    }
+}
+
+pub fn print_results(results: &[RampResult], target_ms: f32) {
+    println!("Largest object count with p99 frame time <= {:.2} ms:", target_ms);
+    println!("{:<6} {:<10} {:>10} {:>10}", "mode", "shape", "max_count", "p99_ms");
+    for r in results {
+        println!(
+            "{:<6} {:<10} {:>10} {:>10.2}{}",
+            r.mode.name(),
+            r.shape.name(),
+            r.max_count.map_or("-".to_string(), |count| count.to_string()),
+            r.p99_ms,
+            if r.capped { "  (MAX_OBJECTS reached)" } else { "" }
+        );
+    }
+}
+
+pub fn write_results(path: &Path, metadata: &[(&str, String)], results: &[RampResult]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (key, value) in metadata {
+        writeln!(writer, "# {}={}", key, value)?;
+    }
+    writeln!(writer, "{}", HEADER)?;
+    for r in results {
+        writeln!(
+            writer,
+            "{},{},{},{:.3},{}",
+            r.mode.name(),
+            r.shape.name(),
+            r.max_count.unwrap_or(0),
+            r.p99_ms,
+            r.capped
+        )?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run a ramp without settle or hold time, so every update measures a
+    // step, with frame times given by `frame_ms` for the current count
+    fn run(ramp: &mut Ramp, frame_ms: impl Fn(i32) -> f32) {
+        let mut count = 0;
+        for frame in 0..10_000 {
+            if ramp.finished() {
+                return;
+            }
+            for action in ramp.update(frame as f64, frame_ms(count)) {
+                if let Action::SetCount(next) = action {
+                    count = next;
+                }
+            }
+        }
+        panic!("the ramp never finished");
+    }
+
+    #[test]
+    fn the_ladder_goes_one_two_five() {
+        let mut count = 10;
+        let mut ladder = vec![count];
+        while count < 10_000 {
+            count = ladder_after(count);
+            ladder.push(count);
+        }
+        assert_eq!(ladder, [10, 20, 50, 100, 200, 500, 1000, 2000, 5000, 10_000]);
+        // Counts off the ladder go to the next step up
+        assert_eq!(ladder_after(30), 50);
+        assert_eq!(ladder_after(750), 1000);
+    }
+
+    #[test]
+    fn bisection_lands_just_under_the_limit() {
+        // 1 ms per 100 objects, so 1600 objects take 16 ms
+        let mut ramp = Ramp::new(16.0, 0.0, 0.0, false);
+        run(&mut ramp, |count| count as f32 / 100.0);
+        assert_eq!(ramp.results().len(), 7);
+        for result in ramp.results() {
+            let best = result.max_count.unwrap();
+            assert!(best <= 1600 && best as f32 >= 1600.0 * (1.0 - PRECISION), "{}", best);
+            assert!(result.p99_ms <= 16.0);
+            assert!(!result.capped);
+        }
+    }
+
+    #[test]
+    fn a_first_step_over_budget_has_no_result() {
+        let mut ramp = Ramp::new(16.0, 0.0, 0.0, true);
+        run(&mut ramp, |_| 100.0);
+        assert_eq!(ramp.results().len(), 8);
+        assert!(ramp.results().iter().all(|result| result.max_count.is_none()));
+    }
+
+    #[test]
+    fn a_search_that_never_fails_stops_at_max_objects() {
+        let mut ramp = Ramp::new(16.0, 0.0, 0.0, false);
+        run(&mut ramp, |_| 1.0);
+        assert!(ramp.results().iter().all(|result| result.capped && result.max_count == Some(MAX_OBJECTS)));
+    }
+
+    #[test]
+    fn only_measured_frames_count() {
+        // Settle frames that blow the budget are thrown away
+        let mut ramp = Ramp::new(16.0, 1.0, 1.0, false);
+        ramp.update(0.0, 0.0);
+        assert!(ramp.update(0.5, 500.0).is_empty());
+        assert!(ramp.update(1.5, 10.0).is_empty());
+        assert!(matches!(ramp.update(2.0, 10.0).as_slice(), [Action::SetCount(20)]));
+    }
+}
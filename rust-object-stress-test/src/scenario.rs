@@ -0,0 +1,200 @@
+use std::fs;
+use std::path::Path;
+
+use crate::lighting::Shading;
+use crate::postfx::Pass;
+use crate::{Blend, RenderMode, ShapeType, MAX_OBJECTS};
+
+// Something a scenario step (or a key press) asks the stress test to do
+#[derive(Clone, Copy)]
+pub enum Action {
+    SetMode(RenderMode),
+    ToggleMode,
+    SetShape(ShapeType),
+    CycleShape,
+    SetBase(i32),
+    AdjustBase(i32),
+    SetPower(i32),
+    AdjustPower(i32),
+    SetCount(i32),
+    // Mouse wheel notches; ten make a factor of 10
+    ScaleCount(f32),
+    SetPhysics(bool),
+    TogglePhysics,
+    SetBlend(Blend),
+    CycleBlend,
+    SetOverdraw(f32),
+    CycleOverdraw,
+    SetWireframe(bool),
+    ToggleWireframe,
+    SetShading(Shading),
+    CycleShading,
+    SetPost(bool),
+    TogglePost,
+    SetPass(Pass, bool),
+    TogglePass(Pass),
+}
+
+// A scripted run, one step per line:
+//
+//   # comment
+//   wait 3        # let this much time pass (seconds)
+//   mode 3d       # 2d | 3d
+//   shape mixed   # 2d: circle | rectangle | mixed, 3d: cube | sphere | cylinder | plane | model
+//   base 500      # 10..1000
+//   power 2       # 1..5, total objects = base * 10^(power-1)
+//   count 7500    # exact total, overrides base/power
+//   physics on    # on | off
+//   blend alpha   # opaque | alpha | additive
+//   overdraw 4    # draw objects squeezed into 1/4 of the area
+//   wireframe off # on | off, edges over the 3D objects
+//   shading pixel # unlit | vertex | pixel, lighting of the 3D objects
+//   post on       # on | off, draw the scene into a render texture first
+//   bloom on      # blur | bloom | grade on | off, post-processing passes
+//
+// A shape step switches to the shape's mode; switching modes starts the new
+// mode with its first shape (circle or cube).
+//
+// The run ends once the last step has been reached.
+pub struct Scenario {
+    steps: Vec<(f64, Action)>,
+    end_time: f64,
+    next: usize,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Scenario::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Scenario, String> {
+        let mut steps = Vec::new();
+        let mut time = 0.0;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let error = |message: &str| format!("line {}: {}", index + 1, message);
+
+            match words.as_slice() {
+                [] => {}
+                ["wait", seconds] => {
+                    // A NaN would keep the run from ever finishing
+                    let seconds = match seconds.parse::<f64>() {
+                        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => seconds,
+                        _ => return Err(error("wait needs a number of seconds, 0 or more")),
+                    };
+                    time += seconds;
+                }
+                ["mode", "2d"] => steps.push((time, Action::SetMode(RenderMode::Mode2D))),
+                ["mode", "3d"] => steps.push((time, Action::SetMode(RenderMode::Mode3D))),
+                ["shape", "circle"] => steps.push((time, Action::SetShape(ShapeType::Circle))),
+                ["shape", "rectangle"] => steps.push((time, Action::SetShape(ShapeType::Rectangle))),
+                ["shape", "mixed"] => steps.push((time, Action::SetShape(ShapeType::Mixed))),
+                ["shape", "cube"] => steps.push((time, Action::SetShape(ShapeType::Cube))),
+                ["shape", "sphere"] => steps.push((time, Action::SetShape(ShapeType::Sphere))),
+                ["shape", "cylinder"] => steps.push((time, Action::SetShape(ShapeType::Cylinder))),
+                ["shape", "plane"] => steps.push((time, Action::SetShape(ShapeType::Plane))),
+                ["shape", "model"] => steps.push((time, Action::SetShape(ShapeType::Model))),
+                ["base", base] => match base.parse() {
+                    Ok(base @ 10..=1000) => steps.push((time, Action::SetBase(base))),
+                    _ => return Err(error("base must be between 10 and 1000")),
+                },
+                ["power", power] => match power.parse() {
+                    Ok(power @ 1..=5) => steps.push((time, Action::SetPower(power))),
+                    _ => return Err(error("power must be between 1 and 5")),
+                },
+                ["count", count] => match count.parse() {
+                    Ok(count @ 0..=MAX_OBJECTS) => steps.push((time, Action::SetCount(count))),
+                    _ => return Err(error(&format!("count must be between 0 and {}", MAX_OBJECTS))),
+                },
+                ["physics", "on"] => steps.push((time, Action::SetPhysics(true))),
+                ["physics", "off"] => steps.push((time, Action::SetPhysics(false))),
+                ["blend", "opaque"] => steps.push((time, Action::SetBlend(Blend::Opaque))),
+                ["blend", "alpha"] => steps.push((time, Action::SetBlend(Blend::Alpha))),
+                ["blend", "additive"] => steps.push((time, Action::SetBlend(Blend::Additive))),
+                ["wireframe", "on"] => steps.push((time, Action::SetWireframe(true))),
+                ["wireframe", "off"] => steps.push((time, Action::SetWireframe(false))),
+                ["shading", "unlit"] => steps.push((time, Action::SetShading(Shading::Unlit))),
+                ["shading", "vertex"] => steps.push((time, Action::SetShading(Shading::Vertex))),
+                ["shading", "pixel"] => steps.push((time, Action::SetShading(Shading::Pixel))),
+                ["post", "on"] => steps.push((time, Action::SetPost(true))),
+                ["post", "off"] => steps.push((time, Action::SetPost(false))),
+                [pass @ ("blur" | "bloom" | "grade"), state @ ("on" | "off")] => {
+                    let pass = match *pass {
+                        "blur" => Pass::Blur,
+                        "bloom" => Pass::Bloom,
+                        _ => Pass::Grade,
+                    };
+                    steps.push((time, Action::SetPass(pass, *state == "on")));
+                }
+                ["overdraw", factor] => match factor.parse::<f32>() {
+                    Ok(factor) if factor >= 1.0 => steps.push((time, Action::SetOverdraw(factor))),
+                    _ => return Err(error("overdraw must be a number >= 1")),
+                },
+                _ => return Err(error(&format!("unknown step '{}'", line))),
+            }
+        }
+
+        Ok(Scenario { steps, end_time: time, next: 0 })
+    }
+
+    // Actions that have come due by `elapsed` seconds into the run
+    pub fn due(&mut self, elapsed: f64) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Some(&(time, action)) = self.steps.get(self.next) {
+            if time > elapsed {
+                break;
+            }
+            actions.push(action);
+            self.next += 1;
+        }
+        actions
+    }
+
+    pub fn finished(&self, elapsed: f64) -> bool {
+        self.next == self.steps.len() && elapsed >= self.end_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_come_due_after_their_waits() {
+        let mut scenario = Scenario::parse("mode 3d\nshape sphere\nwait 2  # settle\ncount 7500\nbloom on\nwait 1\n").unwrap();
+        assert!(matches!(
+            scenario.due(0.0).as_slice(),
+            [Action::SetMode(RenderMode::Mode3D), Action::SetShape(ShapeType::Sphere)]
+        ));
+        assert!(scenario.due(1.9).is_empty());
+        assert!(matches!(scenario.due(2.0).as_slice(), [Action::SetCount(7500), Action::SetPass(Pass::Bloom, true)]));
+        assert!(!scenario.finished(2.5));
+        assert!(scenario.finished(3.0));
+    }
+
+    #[test]
+    fn values_are_range_checked() {
+        assert!(Scenario::parse("base 9").is_err());
+        assert!(Scenario::parse("power 6").is_err());
+        assert!(Scenario::parse(&format!("count {}", MAX_OBJECTS + 1)).is_err());
+        assert!(Scenario::parse("overdraw 0.5").is_err());
+        assert!(Scenario::parse("overdraw NaN").is_err());
+        assert!(Scenario::parse(&format!("base 10\npower 5\ncount {}\noverdraw 4", MAX_OBJECTS)).is_ok());
+    }
+
+    #[test]
+    fn bad_steps_name_their_line() {
+        assert_eq!(Scenario::parse("mode 2d\nshape hexagon").err().unwrap(), "line 2: unknown step 'shape hexagon'");
+    }
+
+    #[test]
+    fn waits_must_be_finite_and_not_negative() {
+        for wait in ["wait -1", "wait NaN", "wait inf", "wait soon"] {
+            assert!(Scenario::parse(wait).is_err(), "{}", wait);
+        }
+        assert!(Scenario::parse("wait 0").is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+use raylib::ffi;
+use raylib::prelude::*;
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+// Frame capture for bug reports: every Nth frame, or the frames around a
+// stall, saved as a PNG sequence or piped to ffmpeg as a video. Reading a
+// frame back and encoding it takes much longer than drawing it, so callers
+// time it and take it out of the frame time.
+
+pub struct CaptureConfig {
+    // Save every Nth frame
+    pub every: Option<u64>,
+    // Save the frames before and after each stall, `window` of each
+    pub stalls: bool,
+    pub window: usize,
+    pub dir: PathBuf,
+    // Pipe the frames to ffmpeg instead of writing PNGs
+    pub video: Option<PathBuf>,
+}
+
+impl CaptureConfig {
+    pub fn enabled(&self) -> bool {
+        self.every.is_some() || self.stalls
+    }
+}
+
+enum Sink {
+    Png(PathBuf),
+    Video { ffmpeg: Child, stdin: Option<ChildStdin> },
+}
+
+pub struct Capture {
+    config: CaptureConfig,
+    sink: Sink,
+    // The last frames, in case the next frame time shows one of them stalled
+    recent: VecDeque<(u64, Image)>,
+    // Frames still to save after a stall
+    after: usize,
+    pub saved: usize,
+    failed: bool,
+}
+
+impl Capture {
+    // `fps` is the rate frames are drawn at, None when there is no steady one
+    pub fn start(config: CaptureConfig, width: i32, height: i32, fps: Option<u32>) -> Result<Capture, String> {
+        let sink = match &config.video {
+            Some(path) => {
+                let mut command = Command::new("ffmpeg");
+                command
+                    .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pixel_format", "rgba"])
+                    .args(["-video_size", &format!("{}x{}", width, height)]);
+                match fps {
+                    // Every Nth frame plays back at fps/N, so the video runs in real time
+                    Some(fps) => {
+                        let rate = fps as f64 / config.every.unwrap_or(1) as f64;
+                        command.args(["-framerate", &rate.to_string(), "-i", "-"]);
+                    }
+                    // Uncapped, each frame is stamped with the time it arrives
+                    None => {
+                        command.args(["-use_wallclock_as_timestamps", "1", "-i", "-", "-fps_mode", "passthrough"]);
+                    }
+                }
+                let mut ffmpeg = command
+                    .args(["-pix_fmt", "yuv420p"])
+                    .arg(path)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("failed to start ffmpeg: {}", e))?;
+                let stdin = ffmpeg.stdin.take();
+                Sink::Video { ffmpeg, stdin }
+            }
+            None => {
+                fs::create_dir_all(&config.dir).map_err(|e| format!("{}: {}", config.dir.display(), e))?;
+                Sink::Png(config.dir.clone())
+            }
+        };
+
+        Ok(Capture {
+            config,
+            sink,
+            recent: VecDeque::new(),
+            after: 0,
+            saved: 0,
+            failed: false,
+        })
+    }
+
+    // Capture the fully drawn frame before it is presented. `previous_stalled`
+    // tells whether the frame before this one went over the stall threshold.
+    pub fn frame(&mut self, d: &mut RaylibDrawHandle, thread: &RaylibThread, frame: u64, previous_stalled: bool) {
+        if self.failed {
+            return;
+        }
+
+        if self.config.stalls && previous_stalled {
+            // The stalled frame is the newest one kept
+            while let Some((number, image)) = self.recent.pop_front() {
+                self.save(number, &image);
+            }
+            self.after = self.config.window;
+        }
+
+        let every = self.config.every.is_some_and(|n| frame.is_multiple_of(n));
+        let around_stall = self.after > 0;
+        if every || self.config.stalls {
+            // Everything drawn so far has to reach the backbuffer first
+            unsafe { ffi::rlDrawRenderBatchActive() };
+            let image = d.load_image_from_screen(thread);
+            if every || around_stall {
+                self.save(frame, &image);
+                self.after = self.after.saturating_sub(1);
+            } else {
+                self.recent.push_back((frame, image));
+                if self.recent.len() > self.config.window {
+                    self.recent.pop_front();
+                }
+            }
+        }
+    }
+
+    fn save(&mut self, frame: u64, image: &Image) {
+        let result = match &mut self.sink {
+            Sink::Png(dir) => {
+                // raylib-rs's export_image drops ExportImage's result
+                let path = dir.join(format!("frame-{:06}.png", frame));
+                let c_path = CString::new(path.to_string_lossy().as_bytes()).map_err(io::Error::other);
+                c_path.and_then(|c_path| {
+                    if unsafe { ffi::ExportImage(*image.as_ref(), c_path.as_ptr()) } {
+                        Ok(())
+                    } else {
+                        Err(io::Error::other(format!("failed to write {}", path.display())))
+                    }
+                })
+            }
+            Sink::Video { stdin: Some(stdin), .. } => {
+                let pixels: Vec<u8> = image.get_image_data().iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+                stdin.write_all(&pixels)
+            }
+            Sink::Video { stdin: None, .. } => Ok(()),
+        };
+        match result {
+            Ok(()) => self.saved += 1,
+            Err(e) => {
+                eprintln!("Frame capture stopped: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let what = match (self.config.every, self.config.stalls) {
+            (Some(n), true) => format!("every {} frames and around stalls", n),
+            (Some(n), false) => format!("every {} frames", n),
+            (None, _) => "around stalls".to_string(),
+        };
+        let to = match &self.config.video {
+            Some(path) => path.display().to_string(),
+            None => self.config.dir.display().to_string(),
+        };
+        format!("{} to {}", what, to)
+    }
+}
+
+impl Drop for Capture {
+    // Let ffmpeg finish the video
+    fn drop(&mut self) {
+        if let Sink::Video { ffmpeg, stdin } = &mut self.sink {
+            drop(stdin.take());
+            match ffmpeg.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => eprintln!("ffmpeg exited with {}", status),
+                Err(e) => eprintln!("Failed to wait for ffmpeg: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The one clock all frame and phase timings are taken from, in whole
+// nanoseconds. raylib's get_frame_time is f32 seconds from its own timer, so
+// mixing it with Instant-based phase times made sub-millisecond pauses hard
+// to line up; it is only kept as a cross-check (see ClockCheck).
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClockSource {
+    // std::time::Instant (clock_gettime(CLOCK_MONOTONIC) on Linux)
+    Instant,
+    // The CPU's timestamp counter, calibrated against Instant at startup;
+    // cheaper to read, x86_64 with an invariant TSC only
+    Tsc,
+}
+
+impl ClockSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockSource::Instant => "instant",
+            ClockSource::Tsc => "tsc",
+        }
+    }
+}
+
+// A point in time, in nanoseconds since the clock was created
+#[derive(Clone, Copy)]
+pub struct Tick(u64);
+
+impl Tick {
+    pub fn ns_since(self, earlier: Tick) -> u64 {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+pub struct Clock {
+    pub source: ClockSource,
+    origin: Instant,
+    // TSC reading at the origin and nanoseconds per tick
+    tsc: Option<(u64, f64)>,
+}
+
+// How long the TSC is measured against Instant
+const CALIBRATION: Duration = Duration::from_millis(50);
+
+impl Clock {
+    // Falls back to Instant, with a note, where there is no usable TSC
+    pub fn new(source: ClockSource) -> Clock {
+        let mut clock = Clock { source: ClockSource::Instant, origin: Instant::now(), tsc: None };
+        if source == ClockSource::Tsc {
+            match calibrate_tsc() {
+                Some((origin, tsc_origin, ns_per_tick)) => {
+                    clock = Clock { source, origin, tsc: Some((tsc_origin, ns_per_tick)) };
+                }
+                None => println!("No invariant TSC on this CPU, using Instant as the clock"),
+            }
+        }
+        clock
+    }
+
+    pub fn now(&self) -> Tick {
+        match self.tsc {
+            Some((origin, ns_per_tick)) => Tick((read_tsc().wrapping_sub(origin) as f64 * ns_per_tick) as u64),
+            None => Tick(self.origin.elapsed().as_nanos() as u64),
+        }
+    }
+
+    pub fn ns_since(&self, start: Tick) -> u64 {
+        self.now().ns_since(start)
+    }
+}
+
+pub fn ms(ns: u64) -> f32 {
+    (ns as f64 / 1_000_000.0) as f32
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_tsc() -> u64 {
+    // Reading the counter has no preconditions
+    unsafe { std::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn read_tsc() -> u64 {
+    0
+}
+
+// The Instant and TSC readings the clock starts from, and the TSC's
+// nanoseconds per tick
+#[cfg(target_arch = "x86_64")]
+fn calibrate_tsc() -> Option<(Instant, u64, f64)> {
+    // Without an invariant TSC the rate changes with the CPU frequency
+    let invariant = std::arch::x86_64::__cpuid(0x8000_0007).edx & (1 << 8) != 0;
+    if !invariant {
+        return None;
+    }
+    let (start, tsc_start) = (Instant::now(), read_tsc());
+    thread::sleep(CALIBRATION);
+    let (end, tsc_end) = (Instant::now(), read_tsc());
+    let ns_per_tick = (end - start).as_nanos() as f64 / tsc_end.wrapping_sub(tsc_start) as f64;
+    Some((end, tsc_end, ns_per_tick))
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn calibrate_tsc() -> Option<(Instant, u64, f64)> {
+    None
+}
+
+// Compares our frame times with raylib's get_frame_time. The two measure from
+// slightly different points of the frame, so single frames differ by some
+// jitter, but over the run they have to add up to the same time.
+#[derive(Default)]
+pub struct ClockCheck {
+    ours_ns: u64,
+    raylib_s: f64,
+    // Largest single-frame difference, in ms
+    pub max_frame_ms: f32,
+}
+
+impl ClockCheck {
+    pub fn add(&mut self, ours_ns: u64, raylib_s: f32) {
+        self.ours_ns += ours_ns;
+        self.raylib_s += raylib_s as f64;
+        self.max_frame_ms = self.max_frame_ms.max((ms(ours_ns) - raylib_s * 1000.0).abs());
+    }
+
+    // How far raylib's total is ahead of ours, in ms
+    pub fn drift_ms(&self) -> f64 {
+        self.raylib_s * 1000.0 - self.ours_ns as f64 / 1_000_000.0
+    }
+}
@@ -0,0 +1,8 @@
+// What the memory and object stress tests share: frame capture, the clock
+// and frame pacing, per-phase run reports and background memory sampling.
+
+pub mod capture;
+pub mod clock;
+pub mod pacing;
+pub mod report;
+pub mod sampler;
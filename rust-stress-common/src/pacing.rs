@@ -0,0 +1,114 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::clock::{Clock, Tick};
+
+// How frames are paced. A limiter that sleeps out the rest of each frame
+// hides any hiccup shorter than that slack, so pauses are easier to see
+// uncapped, or with a busy-waiting limiter that doesn't oversleep.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pacing {
+    // No waiting at all
+    Uncapped,
+    // Sleep out the rest of each frame at the given FPS
+    Fixed(u32),
+    // Let the buffer swap wait for the display's refresh
+    Vsync,
+    // Spin out the rest of each frame at the given FPS
+    Busy(u32),
+}
+
+impl Pacing {
+    // uncapped, fixed:<fps>, vsync or busy:<fps>
+    pub fn parse(value: &str) -> Option<Pacing> {
+        let fps = |fps: &str| fps.parse().ok().filter(|&fps| fps > 0);
+        match value.split_once(':') {
+            None if value == "uncapped" => Some(Pacing::Uncapped),
+            None if value == "vsync" => Some(Pacing::Vsync),
+            Some(("fixed", rate)) => fps(rate).map(Pacing::Fixed),
+            Some(("busy", rate)) => fps(rate).map(Pacing::Busy),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Pacing::Uncapped => "uncapped".to_string(),
+            Pacing::Fixed(fps) => format!("fixed:{}", fps),
+            Pacing::Vsync => "vsync".to_string(),
+            Pacing::Busy(fps) => format!("busy:{}", fps),
+        }
+    }
+
+    // The FPS the limiter paces to, if there is a limiter
+    pub fn fps(&self) -> Option<u32> {
+        match self {
+            Pacing::Fixed(fps) | Pacing::Busy(fps) => Some(*fps),
+            Pacing::Uncapped | Pacing::Vsync => None,
+        }
+    }
+
+    // The frame time in ns the limiter paces to
+    fn frame_time(&self) -> Option<u64> {
+        self.fps().map(|fps| 1_000_000_000 / fps as u64)
+    }
+
+    // Wait until one frame time after `frame_start`. `excluded_ns` is time that
+    // shouldn't count against the frame (frame capture) and moves the
+    // deadline back. Returns the nanoseconds waited.
+    pub fn wait(&self, clock: &Clock, frame_start: Tick, excluded_ns: u64) -> u64 {
+        let Some(frame_time) = self.frame_time() else {
+            return 0;
+        };
+        let start = clock.now();
+        let deadline = frame_time + excluded_ns;
+        let remaining = deadline.saturating_sub(start.ns_since(frame_start));
+        // Sleeping can overshoot by about a scheduler tick, so even the
+        // sleeping limiter spins out the end, like raylib's WaitTime does
+        if matches!(self, Pacing::Fixed(_)) && remaining > SPIN_NS {
+            thread::sleep(Duration::from_nanos(remaining - SPIN_NS));
+        }
+        while clock.ns_since(frame_start) < deadline {
+            std::hint::spin_loop();
+        }
+        clock.ns_since(start)
+    }
+}
+
+// What the sleeping limiter leaves to spinning
+const SPIN_NS: u64 = 2_000_000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_mode() {
+        assert!(Pacing::parse("uncapped") == Some(Pacing::Uncapped));
+        assert!(Pacing::parse("vsync") == Some(Pacing::Vsync));
+        assert!(Pacing::parse("fixed:144") == Some(Pacing::Fixed(144)));
+        assert!(Pacing::parse("busy:60") == Some(Pacing::Busy(60)));
+    }
+
+    #[test]
+    fn rejects_rates_that_are_missing_zero_or_not_numbers() {
+        for value in ["fixed", "fixed:", "fixed:0", "busy:-60", "busy:fast", "vsync:60", "capped", ""] {
+            assert!(Pacing::parse(value).is_none(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn names_parse_back() {
+        for pacing in [Pacing::Uncapped, Pacing::Fixed(60), Pacing::Vsync, Pacing::Busy(240)] {
+            assert!(Pacing::parse(&pacing.name()) == Some(pacing));
+        }
+    }
+
+    #[test]
+    fn only_limiters_have_a_frame_time() {
+        assert_eq!(Pacing::Fixed(50).frame_time(), Some(20_000_000));
+        assert_eq!(Pacing::Busy(1000).frame_time(), Some(1_000_000));
+        assert_eq!(Pacing::Uncapped.frame_time(), None);
+        assert_eq!(Pacing::Vsync.fps(), None);
+    }
+}
@@ -0,0 +1,309 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = "phase,frames,p50_ms,p90_ms,p99_ms,max_ms,stalls,memory_delta_mb,memory_peak_mb";
+
+// Frame times and memory figures for one phase of a run
+pub struct PhaseSummary {
+    pub phase: String,
+    pub frames: usize,
+    pub p50_ms: f32,
+    pub p90_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+    pub stalls: usize,
+    pub memory_delta_mb: f32,
+    pub memory_peak_mb: f32,
+}
+
+struct PhaseSamples {
+    phase: String,
+    frame_ms: Vec<f32>,
+    memory_delta_mb: f32,
+    memory_peak_mb: f32,
+}
+
+// Collects every frame of a run, grouped by phase in order of first
+// appearance. Phases that are visited more than once are pooled, and their
+// memory delta is the sum of the growth during each visit.
+#[derive(Default)]
+pub struct RunRecorder {
+    phases: Vec<PhaseSamples>,
+    current: Option<usize>,
+    last_memory_mb: f32,
+}
+
+impl RunRecorder {
+    pub fn record(&mut self, phase: &str, frame_ms: f32, memory_mb: f32) {
+        let index = match self.current {
+            Some(index) if self.phases[index].phase == phase => {
+                self.phases[index].memory_delta_mb += memory_mb - self.last_memory_mb;
+                index
+            }
+            _ => match self.phases.iter().position(|p| p.phase == phase) {
+                Some(index) => index,
+                None => {
+                    self.phases.push(PhaseSamples {
+                        phase: phase.to_string(),
+                        frame_ms: Vec::new(),
+                        memory_delta_mb: 0.0,
+                        memory_peak_mb: 0.0,
+                    });
+                    self.phases.len() - 1
+                }
+            },
+        };
+
+        let samples = &mut self.phases[index];
+        samples.frame_ms.push(frame_ms);
+        samples.memory_peak_mb = samples.memory_peak_mb.max(memory_mb);
+        self.current = Some(index);
+        self.last_memory_mb = memory_mb;
+    }
+
+    pub fn summarize(&self, stall_ms: f32) -> Vec<PhaseSummary> {
+        self.phases
+            .iter()
+            .map(|samples| {
+                let mut sorted = samples.frame_ms.clone();
+                sorted.sort_by(f32::total_cmp);
+                PhaseSummary {
+                    phase: samples.phase.clone(),
+                    frames: sorted.len(),
+                    p50_ms: percentile(&sorted, 50.0),
+                    p90_ms: percentile(&sorted, 90.0),
+                    p99_ms: percentile(&sorted, 99.0),
+                    max_ms: sorted.last().copied().unwrap_or(0.0),
+                    stalls: sorted.iter().filter(|&&t| t > stall_ms).count(),
+                    memory_delta_mb: samples.memory_delta_mb,
+                    memory_peak_mb: samples.memory_peak_mb,
+                }
+            })
+            .collect()
+    }
+}
+
+// Nearest-rank percentile of an ascending slice
+pub fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Write a run report: `# key=value` metadata lines, then one row per phase
+pub fn write_report(path: &Path, metadata: &[(&str, String)], summaries: &[PhaseSummary]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (key, value) in metadata {
+        writeln!(writer, "# {}={}", key, value)?;
+    }
+    writeln!(writer, "{}", HEADER)?;
+    for s in summaries {
+        writeln!(
+            writer,
+            "{},{},{:.3},{:.3},{:.3},{:.3},{},{:.2},{:.2}",
+            s.phase, s.frames, s.p50_ms, s.p90_ms, s.p99_ms, s.max_ms, s.stalls, s.memory_delta_mb, s.memory_peak_mb
+        )?;
+    }
+    writer.flush()
+}
+
+pub fn read_report(path: &Path) -> Result<Vec<PhaseSummary>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+
+    if lines.next() != Some(HEADER) {
+        return Err(format!("{}: not a run report", path.display()));
+    }
+
+    lines
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').collect();
+            let bad = || format!("{}: bad report line '{}'", path.display(), line);
+            if fields.len() != 9 {
+                return Err(bad());
+            }
+            let number = |i: usize| fields[i].parse::<f32>().map_err(|_| bad());
+            let count = |i: usize| fields[i].parse::<usize>().map_err(|_| bad());
+            Ok(PhaseSummary {
+                phase: fields[0].to_string(),
+                frames: count(1)?,
+                p50_ms: number(2)?,
+                p90_ms: number(3)?,
+                p99_ms: number(4)?,
+                max_ms: number(5)?,
+                stalls: count(6)?,
+                memory_delta_mb: number(7)?,
+                memory_peak_mb: number(8)?,
+            })
+        })
+        .collect()
+}
+
+// How far a run may drift from its baseline before it counts as a regression
+pub struct Tolerances {
+    // Allowed increase of p50/p90/p99 frame time, in percent
+    pub frame_percent: f32,
+    // Allowed number of extra stalls per phase
+    pub stalls: usize,
+    // Allowed increase of peak memory, in percent
+    pub memory_percent: f32,
+}
+
+// Print a metric-by-metric diff against the baseline and return the number
+// of regressions. A phase missing from the current run counts as one.
+pub fn compare_with_baseline(baseline: &[PhaseSummary], current: &[PhaseSummary], tolerances: &Tolerances) -> usize {
+    let mut regressions = 0;
+
+    println!(
+        "{:<24} {:<16} {:>10} {:>10} {:>9} {:>9}",
+        "phase", "metric", "baseline", "current", "change", "limit"
+    );
+    for base in baseline {
+        let Some(run) = current.iter().find(|s| s.phase == base.phase) else {
+            println!("{:<24} missing from this run  REGRESSION", base.phase);
+            regressions += 1;
+            continue;
+        };
+
+        let relative = [
+            ("p50_ms", base.p50_ms, run.p50_ms, tolerances.frame_percent),
+            ("p90_ms", base.p90_ms, run.p90_ms, tolerances.frame_percent),
+            ("p99_ms", base.p99_ms, run.p99_ms, tolerances.frame_percent),
+            ("memory_peak_mb", base.memory_peak_mb, run.memory_peak_mb, tolerances.memory_percent),
+        ];
+        for (metric, before, after, limit) in relative {
+            let change = if before > 0.0 { (after - before) / before * 100.0 } else { 0.0 };
+            let regressed = change > limit;
+            println!(
+                "{:<24} {:<16} {:>10.2} {:>10.2} {:>+8.1}% {:>+8.1}%{}",
+                base.phase,
+                metric,
+                before,
+                after,
+                change,
+                limit,
+                if regressed { "  REGRESSION" } else { "" }
+            );
+            regressions += regressed as usize;
+        }
+
+        let regressed = run.stalls > base.stalls + tolerances.stalls;
+        println!(
+            "{:<24} {:<16} {:>10} {:>10} {:>+9} {:>+9}{}",
+            base.phase,
+            "stalls",
+            base.stalls,
+            run.stalls,
+            run.stalls as i64 - base.stalls as i64,
+            tolerances.stalls,
+            if regressed { "  REGRESSION" } else { "" }
+        );
+        regressions += regressed as usize;
+    }
+
+    for run in current {
+        if !baseline.iter().any(|s| s.phase == run.phase) {
+            println!("{:<24} not in baseline, not compared", run.phase);
+        }
+    }
+
+    regressions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(phase: &str, p50_ms: f32, stalls: usize, memory_peak_mb: f32) -> PhaseSummary {
+        PhaseSummary {
+            phase: phase.to_string(),
+            frames: 100,
+            p50_ms,
+            p90_ms: p50_ms,
+            p99_ms: p50_ms,
+            max_ms: p50_ms,
+            stalls,
+            memory_delta_mb: 0.0,
+            memory_peak_mb,
+        }
+    }
+
+    const TOLERANCES: Tolerances = Tolerances { frame_percent: 10.0, stalls: 2, memory_percent: 10.0 };
+
+    #[test]
+    fn percentile_is_nearest_rank() {
+        let sorted: Vec<f32> = (1..=10).map(|i| i as f32).collect();
+        assert_eq!(percentile(&sorted, 50.0), 5.0);
+        assert_eq!(percentile(&sorted, 99.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn revisited_phases_are_pooled() {
+        let mut recorder = RunRecorder::default();
+        recorder.record("idle", 10.0, 100.0);
+        recorder.record("stress", 20.0, 110.0);
+        recorder.record("stress", 40.0, 130.0);
+        recorder.record("idle", 10.0, 120.0);
+        recorder.record("stress", 20.0, 125.0);
+        recorder.record("stress", 20.0, 135.0);
+
+        let summaries = recorder.summarize(30.0);
+        assert_eq!(summaries.len(), 2);
+        let stress = &summaries[1];
+        assert_eq!(stress.phase, "stress");
+        assert_eq!(stress.frames, 4);
+        assert_eq!(stress.stalls, 1);
+        assert_eq!(stress.max_ms, 40.0);
+        // 110 -> 130, then 125 -> 135
+        assert_eq!(stress.memory_delta_mb, 30.0);
+        assert_eq!(stress.memory_peak_mb, 135.0);
+    }
+
+    #[test]
+    fn reports_read_back_what_was_written() {
+        let path = std::env::temp_dir().join(format!("stress-report-{}.csv", std::process::id()));
+        let metadata = [("seed", "42".to_string())];
+        write_report(&path, &metadata, &[summary("idle", 16.6, 0, 50.0), summary("stress-1", 18.25, 3, 80.5)]).unwrap();
+        let read = read_report(&path);
+        let _ = fs::remove_file(&path);
+
+        let read = read.unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[1].phase, "stress-1");
+        assert_eq!(read[1].p50_ms, 18.25);
+        assert_eq!(read[1].stalls, 3);
+        assert_eq!(read[1].memory_peak_mb, 80.5);
+    }
+
+    #[test]
+    fn reports_without_the_header_are_rejected() {
+        let path = std::env::temp_dir().join(format!("stress-not-a-report-{}.csv", std::process::id()));
+        fs::write(&path, "frame,time_ms,phase,frame_ms,memory_mb\n0,0,idle,16.6,50\n").unwrap();
+        let read = read_report(&path);
+        let _ = fs::remove_file(&path);
+        assert!(read.is_err());
+    }
+
+    #[test]
+    fn only_changes_past_the_tolerances_regress() {
+        let baseline = [summary("idle", 10.0, 0, 100.0), summary("stress-1", 20.0, 1, 200.0)];
+        let within = [summary("idle", 10.9, 0, 109.0), summary("stress-1", 21.0, 3, 200.0)];
+        assert_eq!(compare_with_baseline(&baseline, &within, &TOLERANCES), 0);
+
+        // p50, p90 and p99 each count, and so do the stalls
+        let slower = [summary("idle", 11.5, 0, 100.0), summary("stress-1", 20.0, 4, 200.0)];
+        assert_eq!(compare_with_baseline(&baseline, &slower, &TOLERANCES), 4);
+    }
+
+    #[test]
+    fn a_missing_phase_is_a_regression_and_a_new_one_is_not() {
+        let baseline = [summary("idle", 10.0, 0, 100.0), summary("stress-1", 20.0, 0, 200.0)];
+        let current = [summary("idle", 10.0, 0, 100.0), summary("stress-2", 20.0, 0, 200.0)];
+        assert_eq!(compare_with_baseline(&baseline, &current, &TOLERANCES), 1);
+    }
+}
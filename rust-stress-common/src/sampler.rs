@@ -0,0 +1,171 @@
+use memory_stats::memory_stats;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Memory usage for the overlay and the logs. Reading it means a trip through
+// /proc on Linux, so by default a background thread polls it and the render
+// loop only loads the latest value. With a rate of 0 it is read inline on
+// every call, as it used to be, which shows what the reads cost the frame.
+// Other slow reads can ride along on the same thread (see start_with).
+
+// The highest sampling rate; much beyond this the thread does little but read
+// /proc, and at a rate over a billion the interval would round to zero
+pub const MAX_HZ: u32 = 10_000;
+
+// Process RSS in MB, 0 if it can't be read
+pub fn read_memory_mb() -> f32 {
+    match memory_stats() {
+        Some(usage) => usage.physical_mem as f32 / (1024.0 * 1024.0),
+        None => 0.0,
+    }
+}
+
+// Extra work done after every memory reading, publishing into Gauges
+pub type Hook = Box<dyn FnMut() + Send>;
+
+// A reading shared with the render loop without locking; f32 bits, NaN when
+// there is none
+pub struct Gauge(AtomicU32);
+
+impl Default for Gauge {
+    fn default() -> Gauge {
+        Gauge(AtomicU32::new(f32::NAN.to_bits()))
+    }
+}
+
+impl Gauge {
+    pub fn set(&self, value: Option<f32>) {
+        self.0.store(value.unwrap_or(f32::NAN).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> Option<f32> {
+        Some(f32::from_bits(self.0.load(Ordering::Relaxed))).filter(|value| !value.is_nan())
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    stop: AtomicBool,
+    // The latest reading, as f32 bits
+    memory_mb: AtomicU32,
+    // Only ever taken by whoever samples, so never contended
+    hook: Mutex<Option<Hook>>,
+    samples: AtomicU64,
+    // Time spent reading, in total and the longest read
+    busy_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl Shared {
+    fn sample(&self) -> f32 {
+        let start = Instant::now();
+        let memory_mb = read_memory_mb();
+        if let Some(hook) = self.hook.lock().unwrap().as_mut() {
+            hook();
+        }
+        let took = start.elapsed().as_nanos() as u64;
+        self.memory_mb.store(memory_mb.to_bits(), Ordering::Relaxed);
+        self.samples.fetch_add(1, Ordering::Relaxed);
+        self.busy_ns.fetch_add(took, Ordering::Relaxed);
+        self.max_ns.fetch_max(took, Ordering::Relaxed);
+        memory_mb
+    }
+}
+
+// What sampling has cost so far
+pub struct SamplerCost {
+    pub samples: u64,
+    pub mean_us: f32,
+    pub max_us: f32,
+    // Share of one core spent reading, hook included
+    pub percent: f32,
+}
+
+// The background thread is stopped and joined on drop
+pub struct Sampler {
+    // Samples per second, 0 = inline
+    pub hz: u32,
+    shared: Arc<Shared>,
+    started: Instant,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    pub fn start(hz: u32) -> Sampler {
+        Sampler::start_with(hz, None)
+    }
+
+    // The hook runs on the sampler thread, or inline with a rate of 0
+    pub fn start_with(hz: u32, hook: Option<Hook>) -> Sampler {
+        let shared = Arc::new(Shared { hook: Mutex::new(hook), ..Shared::default() });
+        // Never start with nothing to show
+        shared.sample();
+        let handle = (hz > 0).then(|| {
+            let shared = Arc::clone(&shared);
+            let interval = Duration::from_secs(1) / hz.min(MAX_HZ);
+            thread::Builder::new()
+                .name("memory-sampler".to_string())
+                .spawn(move || {
+                    let mut next = Instant::now() + interval;
+                    while !shared.stop.load(Ordering::Relaxed) {
+                        // Parked rather than slept, so drop doesn't wait out
+                        // a slow rate
+                        let now = Instant::now();
+                        if now < next {
+                            thread::park_timeout(next - now);
+                            continue;
+                        }
+                        shared.sample();
+                        next += interval;
+                        // Skip what was missed instead of catching up
+                        if next < Instant::now() {
+                            next = Instant::now() + interval;
+                        }
+                    }
+                })
+                .expect("failed to spawn memory sampler thread")
+        });
+        Sampler { hz, shared, started: Instant::now(), handle }
+    }
+
+    // The latest memory usage in MB
+    pub fn memory_mb(&self) -> f32 {
+        if self.handle.is_some() {
+            f32::from_bits(self.shared.memory_mb.load(Ordering::Relaxed))
+        } else {
+            self.shared.sample()
+        }
+    }
+
+    pub fn cost(&self) -> SamplerCost {
+        let samples = self.shared.samples.load(Ordering::Relaxed);
+        let busy_ns = self.shared.busy_ns.load(Ordering::Relaxed);
+        let elapsed_ns = self.started.elapsed().as_nanos().max(1) as f32;
+        SamplerCost {
+            samples,
+            mean_us: busy_ns as f32 / samples.max(1) as f32 / 1000.0,
+            max_us: self.shared.max_ns.load(Ordering::Relaxed) as f32 / 1000.0,
+            percent: busy_ns as f32 / elapsed_ns * 100.0,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if self.hz == 0 {
+            "inline".to_string()
+        } else {
+            format!("{} Hz", self.hz)
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}